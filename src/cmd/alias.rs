@@ -0,0 +1,172 @@
+use crate::{parser, writer::Writer, Result};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+static ALIASES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Characters an alias name can't have, as they'd end the word or quote it.
+const INVALID: &str = " \t\n/$`=|&;()<>'\"\\";
+
+pub fn get(name: &str) -> Option<String> {
+    lock().get(name).cloned()
+}
+
+pub fn names() -> Vec<String> {
+    lock().keys().cloned().collect()
+}
+
+/// `alias [-p] [name[=value] ...]` defines aliases, or prints the named
+/// ones, or all of them without any names.
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let args = match args.first().map(String::as_str) {
+        Some("-p") => &args[1..],
+        Some("--") => &args[1..],
+        Some(opt) if opt.len() > 1 && opt.starts_with('-') => {
+            w.ewriteln(format!("alias: {opt}: invalid option"))?;
+            w.ewriteln("alias: usage: alias [-p] [name[=value] ... ]")?;
+            return Ok(2);
+        }
+        _ => args,
+    };
+
+    if args.is_empty() {
+        for (name, value) in lock().iter() {
+            w.writeln(format!("alias {name}={}", quote(value)))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, _)) if name.is_empty() || name.contains(|c| INVALID.contains(c)) => {
+                w.ewriteln(format!("alias: `{name}': invalid alias name"))?;
+                status = 1;
+            }
+            Some((name, value)) => {
+                lock().insert(name.to_string(), value.to_string());
+            }
+            None => match get(arg) {
+                Some(value) => w.writeln(format!("alias {arg}={}", quote(&value)))?,
+                None => {
+                    w.ewriteln(format!("alias: {arg}: not found"))?;
+                    status = 1;
+                }
+            },
+        }
+    }
+
+    Ok(status)
+}
+
+/// `unalias [-a] name [name ...]` removes the named aliases, or all of them
+/// with `-a`.
+pub fn unalias(args: &[String], w: &mut Writer) -> Result<i32> {
+    const USAGE: &str = "unalias: usage: unalias [-a] name [name ...]";
+
+    let args = match args.first().map(String::as_str) {
+        Some("-a") => {
+            lock().clear();
+            return Ok(0);
+        }
+        Some("--") => &args[1..],
+        Some(opt) if opt.len() > 1 && opt.starts_with('-') => {
+            w.ewriteln(format!("unalias: {opt}: invalid option"))?;
+            w.ewriteln(USAGE)?;
+            return Ok(2);
+        }
+        _ => args,
+    };
+
+    if args.is_empty() {
+        w.ewriteln(USAGE)?;
+        return Ok(2);
+    }
+
+    let mut status = 0;
+    for name in args {
+        if lock().remove(name).is_none() {
+            w.ewriteln(format!("unalias: {name}: not found"))?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Replaces an alias in the first word of `line` with its value, whose own
+/// first word is expanded in turn unless it names an alias already being
+/// expanded. When the value ends with a blank, the word after it is expanded
+/// too. A word with any quoting in it is never an alias.
+pub fn expand(line: &str) -> String {
+    expand_first(line, &mut vec![])
+}
+
+fn expand_first(line: &str, seen: &mut Vec<String>) -> String {
+    let Some(token) = parser::tokens(line).into_iter().next() else {
+        return line.to_string();
+    };
+
+    let word = &line[token.span.clone()];
+    let value = match get(word) {
+        Some(value) if word == token.text && !seen.iter().any(|s| s == word) => value,
+        _ => return line.to_string(),
+    };
+    seen.push(word.to_string());
+
+    let value = expand_first(&value, seen);
+    let rest = &line[token.span.end..];
+    let rest = if value.ends_with(char::is_whitespace) {
+        expand_first(rest, &mut vec![])
+    } else {
+        rest.to_string()
+    };
+
+    format!("{}{value}{rest}", &line[..token.span.start])
+}
+
+/// Single-quotes `value`, so that the alias can be defined again with what
+/// is printed.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<String, String>> {
+    ALIASES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn define(name: &str, value: &str) {
+        lock().insert(name.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn it_expands_aliases() {
+        define("alias-ll", "ls -la");
+        define("alias-l", "alias-ll -h");
+        define("alias-loop", "alias-loop2 x");
+        define("alias-loop2", "alias-loop y");
+        define("alias-sudo", "sudo ");
+
+        assert_eq!(expand("alias-ll src"), "ls -la src");
+        assert_eq!(expand("  alias-l src"), "  ls -la -h src");
+        assert_eq!(expand("alias-loop"), "alias-loop y x");
+        assert_eq!(expand("alias-sudo alias-ll"), "sudo  ls -la");
+        assert_eq!(
+            expand("alias-sudo alias-sudo alias-l"),
+            "sudo  sudo  ls -la -h"
+        );
+        assert_eq!(expand("'alias-ll' src"), "'alias-ll' src");
+        assert_eq!(expand("echo alias-ll"), "echo alias-ll");
+        assert_eq!(expand(""), "");
+    }
+
+    #[test]
+    fn it_quotes_values() {
+        assert_eq!(quote("echo 'hi'"), "'echo '\\''hi'\\'''");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::complete::{self, Context, Spec, Strategy};
+use crate::{vars, writer::Writer, Result};
+
+const COMPLETE_USAGE: &str = "complete: usage: complete [-pr] [-dfc] [-W wordlist] \
+    [-F function] [-C command] [-X filterpat] [name ...]";
+const COMPGEN_USAGE: &str = "compgen: usage: compgen [-V varname] [-dfc] [-W wordlist] \
+    [-F function] [-C command] [-X filterpat] [word]";
+
+/// The options of `complete` and `compgen`, and the arguments after them.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    spec: Spec,
+    /// Whether any option of the spec was given.
+    specified: bool,
+    print: bool,
+    remove: bool,
+    /// `compgen -V`: the array the candidates are put in.
+    var: Option<String>,
+    args: Vec<String>,
+}
+
+/// `complete [-pr] [options] [name ...]` sets up how the arguments of the
+/// named commands are completed, prints the specs with `-p` or without any
+/// options, and removes them with `-r`.
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let opts = match parse(args, true) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            w.ewriteln(format!("complete: {msg}"))?;
+            w.ewriteln(COMPLETE_USAGE)?;
+            return Ok(2);
+        }
+    };
+
+    if opts.remove {
+        if opts.args.is_empty() {
+            complete::remove(None);
+        }
+
+        let mut status = 0;
+        for name in &opts.args {
+            if !complete::remove(Some(name)) {
+                w.ewriteln(format!("complete: {name}: no completion specification"))?;
+                status = 1;
+            }
+        }
+        return Ok(status);
+    }
+
+    if opts.print || !opts.specified {
+        let (specs, missing) = complete::specs(&opts.args);
+
+        for (name, spec) in specs {
+            w.writeln(format!("{spec} {name}"))?;
+        }
+        for name in &missing {
+            w.ewriteln(format!("complete: {name}: no completion specification"))?;
+        }
+        return Ok(if missing.is_empty() { 0 } else { 1 });
+    }
+
+    if opts.args.is_empty() {
+        w.ewriteln(COMPLETE_USAGE)?;
+        return Ok(2);
+    }
+
+    for name in &opts.args {
+        complete::set(name, opts.spec.clone());
+    }
+    Ok(0)
+}
+
+/// `compgen [options] [word]` prints what the options complete `word` with,
+/// or puts it in an array with `-V`.
+pub fn compgen(args: &[String], w: &mut Writer) -> Result<i32> {
+    let opts = match parse(args, false) {
+        Ok(opts) if opts.args.len() <= 1 => opts,
+        Ok(_) => {
+            w.ewriteln(COMPGEN_USAGE)?;
+            return Ok(2);
+        }
+        Err(msg) => {
+            w.ewriteln(format!("compgen: {msg}"))?;
+            w.ewriteln(COMPGEN_USAGE)?;
+            return Ok(2);
+        }
+    };
+
+    let word = opts.args.first().cloned().unwrap_or_default();
+    let ctx = Context {
+        line: &word,
+        point: word.len(),
+        strategy: Strategy::Prefix,
+        words: vec![word.clone()],
+        cword: 0,
+    };
+    let candidates = opts.spec.generate(&ctx);
+    let status = if candidates.is_empty() { 1 } else { 0 };
+
+    if let Some(var) = &opts.var {
+        vars::set_array(var, candidates.into_iter().map(|c| c.word).collect());
+        return Ok(status);
+    }
+
+    for candidate in &candidates {
+        w.writeln(&candidate.word)?;
+    }
+
+    Ok(status)
+}
+
+/// Reads options until the first argument that isn't one. Single-letter
+/// options may be grouped, as in `-df`, and those taking a value accept it
+/// either attached or as the next argument. `-p` and `-r` are only known to
+/// `complete`, and `-V` to `compgen`.
+fn parse(args: &[String], complete: bool) -> std::result::Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+            opts.args.push(arg.clone());
+            break;
+        };
+
+        for (i, flag) in flags.char_indices() {
+            let mut value = || {
+                let attached = &flags[i + 1..];
+                if attached.is_empty() {
+                    args.next()
+                        .cloned()
+                        .ok_or(format!("-{flag}: option requires an argument"))
+                } else {
+                    Ok(attached.to_string())
+                }
+            };
+
+            match flag {
+                'd' => opts.spec.dirs = true,
+                'f' => opts.spec.files = true,
+                'c' => opts.spec.commands = true,
+                'p' if complete => opts.print = true,
+                'r' if complete => opts.remove = true,
+                'W' => opts.spec.words = Some(value()?),
+                'F' => opts.spec.function = Some(value()?),
+                'C' => opts.spec.command = Some(value()?),
+                'X' => opts.spec.filter = Some(value()?),
+                'V' if !complete => opts.var = Some(value()?),
+                _ => return Err(format!("-{flag}: invalid option")),
+            }
+
+            opts.specified |= !matches!(flag, 'p' | 'r' | 'V');
+            if matches!(flag, 'W' | 'F' | 'C' | 'X' | 'V') {
+                break;
+            }
+        }
+    }
+
+    opts.args.extend(args.cloned());
+    Ok(opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn it_parses_options() {
+        let opts = parse(&args("-dW a -X*.o svc -x"), true).unwrap();
+        assert!(opts.specified);
+        assert!(opts.spec.dirs);
+        assert_eq!(opts.spec.words, Some("a".into()));
+        assert_eq!(opts.spec.filter, Some("*.o".into()));
+        assert_eq!(opts.args, args("svc -x"));
+
+        let opts = parse(&args("-p"), true).unwrap();
+        assert!(opts.print && !opts.specified);
+
+        assert_eq!(
+            parse(&args("-r"), false),
+            Err("-r: invalid option".to_string())
+        );
+        assert_eq!(
+            parse(&args("-W"), true),
+            Err("-W: option requires an argument".to_string())
+        );
+
+        let opts = parse(&args("-F_svc svc"), true).unwrap();
+        assert_eq!(opts.spec.function, Some("_svc".into()));
+        assert_eq!(opts.args, args("svc"));
+
+        let opts = parse(&args("-V reply -W a"), false).unwrap();
+        assert_eq!(opts.var, Some("reply".into()));
+        assert_eq!(
+            parse(&args("-V reply"), true),
+            Err("-V: invalid option".to_string())
+        );
+    }
+}
@@ -1,6 +1,39 @@
 use crate::Result;
+use std::ffi::{CStr, CString};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 
+/// The home directory `~user` stands for, or `$HOME` for a bare `~`.
+pub fn home_dir(user: &str) -> Option<String> {
+    if user.is_empty() {
+        return std::env::var("HOME").ok();
+    }
+
+    let name = CString::new(user).ok()?;
+    unsafe {
+        let entry = libc::getpwnam(name.as_ptr());
+        if entry.is_null() {
+            return None;
+        }
+        let dir = CStr::from_ptr((*entry).pw_dir);
+        Some(dir.to_string_lossy().into_owned())
+    }
+}
+
+/// The name of the user running the tests, if the passwd database has one.
+#[cfg(test)]
+pub fn current_user() -> Option<String> {
+    unsafe {
+        let entry = libc::getpwuid(libc::getuid());
+        if entry.is_null() {
+            return None;
+        }
+        let name = CStr::from_ptr((*entry).pw_name);
+        Some(name.to_string_lossy().into_owned())
+    }
+}
+
 pub fn list_dirs(input: &str) -> Vec<&Path> {
     if input.is_empty() {
         return vec![];
@@ -9,7 +42,8 @@ pub fn list_dirs(input: &str) -> Vec<&Path> {
     input
         .split(':')
         .filter_map(|p| {
-            let path = Path::new(p);
+            // An empty entry stands for the current directory.
+            let path = Path::new(if p.is_empty() { "." } else { p });
             if path.is_dir() {
                 Some(path)
             } else {
@@ -31,9 +65,28 @@ pub fn list_files(path: &Path) -> Result<Vec<PathBuf>> {
     Ok(paths)
 }
 
-pub fn filename(name: &str) -> Box<dyn Fn(&PathBuf) -> bool> {
-    let name = name.to_owned();
-    Box::new(move |path| path.file_name().is_some_and(|n| n == name.as_str()))
+/// Whether `path` is a regular file (after following symlinks) that the
+/// current user may execute.
+pub fn is_executable(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(p) => unsafe {
+            libc::faccessat(libc::AT_FDCWD, p.as_ptr(), libc::X_OK, libc::AT_EACCESS) == 0
+        },
+        Err(_) => false,
+    }
+}
+
+/// Whether the first line of the file holds a NUL byte, the way shells tell
+/// binaries apart from scripts without a shebang.
+pub fn is_binary(path: &Path) -> Result<bool> {
+    let mut buf = [0u8; 80];
+    let len = std::fs::File::open(path)?.read(&mut buf)?;
+    let line = buf[..len].split(|b| *b == b'\n').next().unwrap_or_default();
+    Ok(line.contains(&0))
 }
 
 pub fn current_dir() -> Result<PathBuf> {
@@ -49,10 +102,27 @@ pub fn path_stringify(path: PathBuf) -> Option<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn it_tells_binaries_from_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let script = dir.path().join("script");
+        std::fs::write(&script, "echo hello\n\0").unwrap();
+        assert!(!is_binary(&script).unwrap());
+
+        let binary = dir.path().join("binary");
+        std::fs::write(&binary, b"\x7fELF\0\0").unwrap();
+        assert!(is_binary(&binary).unwrap());
+    }
+
     #[test]
     fn it_split_into_directories() {
         let input = "";
         let dirs = list_dirs(input);
         assert!(dirs.is_empty());
+
+        let input = "/::/nonexistent";
+        let dirs = list_dirs(input);
+        assert_eq!(dirs, vec![Path::new("/"), Path::new(".")]);
     }
 }
@@ -0,0 +1,142 @@
+use crate::{parser, vars, Result};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+static FUNCTIONS: Mutex<BTreeMap<String, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+/// Characters a function name can't have, as they'd end the word or quote it.
+const INVALID: &str = " \t\n/$`=|&;()<>'\"\\{}";
+
+/// `name() { command; ... }`, with the commands of its body. The body may
+/// span lines, which end commands as `;` does.
+#[derive(Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub body: Vec<String>,
+}
+
+impl Definition {
+    /// Parses `line` if it defines a function, which fails when the body is
+    /// missing its `}`.
+    pub fn parse(line: &str) -> Option<std::result::Result<Self, String>> {
+        let (name, rest) = header(line)?;
+        let Some(body) = rest.trim_end().strip_suffix('}') else {
+            return Some(Err("syntax error: unexpected end of file".to_string()));
+        };
+
+        let body = parser::commands(body)
+            .into_iter()
+            .filter(|command| !command.starts_with('#'))
+            .map(String::from)
+            .collect();
+        Some(Ok(Self {
+            name: name.to_string(),
+            body,
+        }))
+    }
+}
+
+/// Whether `line` starts a function definition that goes on over the next
+/// lines.
+pub fn opens(line: &str) -> bool {
+    header(line).is_some() && !line.trim_end().ends_with('}')
+}
+
+/// Splits `name() {` off the start of `line`.
+fn header(line: &str) -> Option<(&str, &str)> {
+    let (name, rest) = line.trim_start().split_once('(')?;
+    let name = name.trim_end();
+    let rest = rest.trim_start().strip_prefix(')')?;
+    let rest = rest.trim_start().strip_prefix('{')?;
+
+    let valid = !name.is_empty() && !name.contains(|c| INVALID.contains(c));
+    valid.then_some((name, rest))
+}
+
+pub fn define(definition: Definition) {
+    lock().insert(definition.name, definition.body);
+}
+
+pub fn exists(name: &str) -> bool {
+    lock().contains_key(name)
+}
+
+pub fn names() -> Vec<String> {
+    lock().keys().cloned().collect()
+}
+
+/// Runs the commands of the function `name` in this shell, with `args` as the
+/// positional parameters, and returns the status of the last one. `None`
+/// when there is no such function.
+pub fn call(name: &str, args: &[String]) -> Option<Result<i32>> {
+    let body = lock().get(name).cloned()?;
+    let outer = vars::set_positional(args.to_vec());
+
+    let mut result = Ok(0);
+    for command in &body {
+        result = crate::exec_cmd(command);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    vars::set_positional(outer);
+    Some(result)
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<String, Vec<String>>> {
+    FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_definitions() {
+        let definition = Definition::parse("_svc () { echo 'a;b'; ls\n# list\n}");
+        assert_eq!(
+            definition,
+            Some(Ok(Definition {
+                name: "_svc".into(),
+                body: vec!["echo 'a;b'".into(), "ls".into()],
+            }))
+        );
+
+        assert_eq!(
+            Definition::parse("f() { echo"),
+            Some(Err("syntax error: unexpected end of file".into()))
+        );
+        assert_eq!(Definition::parse("echo (a) {}"), None);
+        assert_eq!(Definition::parse("f()"), None);
+
+        assert!(opens("f() {"));
+        assert!(!opens("f() { :; }"));
+        assert!(!opens("}"));
+    }
+
+    #[test]
+    fn it_calls_functions_with_their_own_arguments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let line = format!(
+            "function-test() {{ echo $# $1 > {0}; echo $2 >> {0}; }}",
+            out.display()
+        );
+        define(Definition::parse(&line).unwrap().unwrap());
+        assert!(exists("function-test"));
+
+        let _state = vars::test_lock();
+        let (outer, outer_status) = (vars::positional(), vars::last_status());
+        vars::set_positional(vec!["outer".into()]);
+        let status = call("function-test", &["a".into(), "b".into()]);
+        let positional = vars::set_positional(outer);
+        vars::set_last_status(outer_status);
+
+        assert_eq!(status.map(Result::ok), Some(Some(0)));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "2 a\nb\n");
+        assert_eq!(positional, vec!["outer"]);
+        assert!(call("function-test-missing", &[]).is_none());
+    }
+}
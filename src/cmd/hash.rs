@@ -0,0 +1,223 @@
+use super::{executable, fs, Lookup};
+use crate::{writer::Writer, Result};
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+static TABLE: Mutex<Table> = Mutex::new(Table {
+    path_var: String::new(),
+    entries: BTreeMap::new(),
+});
+static LISTING: Mutex<Option<Listing>> = Mutex::new(None);
+
+/// Remembered command locations, valid for the `$PATH` they were found with.
+struct Table {
+    path_var: String,
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    path: String,
+    hits: usize,
+}
+
+/// Every executable name in `$PATH`, along with what it was listed from.
+struct Listing {
+    path_var: String,
+    stamps: Vec<Option<SystemTime>>,
+    names: HashSet<String>,
+}
+
+/// Returns the remembered location of `name`, dropping it if the file is gone
+/// and the whole table if `$PATH` changed since it was filled.
+pub fn get(name: &str) -> Option<String> {
+    let mut table = lock();
+    table.sync();
+
+    let path = table.entries.get(name)?.path.clone();
+
+    if fs::is_executable(Path::new(&path)) {
+        Some(path)
+    } else {
+        table.entries.remove(name);
+        None
+    }
+}
+
+/// Records that `name` ran from `path`.
+pub fn hit(name: &str, path: &str) {
+    let mut table = lock();
+    table.sync();
+
+    let entry = table.entries.entry(name.to_string()).or_insert(Entry {
+        path: path.to_string(),
+        hits: 0,
+    });
+
+    if entry.path != path {
+        entry.path = path.to_string();
+        entry.hits = 0;
+    }
+    entry.hits += 1;
+}
+
+/// Returns the executable names found in `dirs`, listing them again only
+/// when `$PATH` or the modification time of one of the directories changed.
+pub fn names(dirs: &[&Path], list: impl FnOnce() -> HashSet<String>) -> HashSet<String> {
+    let path_var = path_var();
+    let stamps: Vec<Option<SystemTime>> = dirs
+        .iter()
+        .map(|dir| dir.metadata().and_then(|m| m.modified()).ok())
+        .collect();
+
+    let mut listing = LISTING.lock().unwrap_or_else(|e| e.into_inner());
+
+    match listing.as_ref() {
+        Some(l) if l.path_var == path_var && l.stamps == stamps => l.names.clone(),
+        _ => {
+            let names = list();
+            *listing = Some(Listing {
+                path_var,
+                stamps,
+                names: names.clone(),
+            });
+            names
+        }
+    }
+}
+
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let mut args = args.iter().map(String::as_str);
+
+    match args.next() {
+        None => print(w),
+        Some("-r") => {
+            lock().entries.clear();
+            *LISTING.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            Ok(0)
+        }
+        Some("-p") => match (args.next(), args.next()) {
+            (Some(path), Some(name)) => {
+                insert(name, path.to_string());
+                Ok(0)
+            }
+            _ => {
+                w.ewriteln("hash: -p: option requires an argument")?;
+                Ok(2)
+            }
+        },
+        Some("-d") => each(args, w, |name, _| lock().entries.remove(name).is_some()),
+        Some("-t") => {
+            let names: Vec<&str> = args.collect();
+            let verbose = names.len() > 1;
+
+            each(names.into_iter(), w, |name, w| match get(name) {
+                Some(path) if verbose => w.writeln(format!("{name}\t{path}")).is_ok(),
+                Some(path) => w.writeln(path).is_ok(),
+                None => false,
+            })
+        }
+        Some(name) => {
+            let names = std::iter::once(name).chain(args);
+
+            each(names, w, |name, _| match executable(name) {
+                Lookup::Found(path) => {
+                    insert(name, path);
+                    true
+                }
+                _ => false,
+            })
+        }
+    }
+}
+
+fn insert(name: &str, path: String) {
+    let mut table = lock();
+    table.sync();
+    table
+        .entries
+        .insert(name.to_string(), Entry { path, hits: 0 });
+}
+
+/// Applies `f` to every name, reporting the ones it fails for as not found.
+fn each<'a>(
+    names: impl Iterator<Item = &'a str>,
+    w: &mut Writer,
+    mut f: impl FnMut(&str, &mut Writer) -> bool,
+) -> Result<i32> {
+    let mut status = 0;
+
+    for name in names {
+        if !f(name, w) {
+            w.ewriteln(format!("hash: {name}: not found"))?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+fn print(w: &mut Writer) -> Result<i32> {
+    let entries: Vec<Entry> = {
+        let mut table = lock();
+        table.sync();
+        table.entries.values().cloned().collect()
+    };
+
+    if entries.is_empty() {
+        w.writeln("hash: hash table empty")?;
+        return Ok(0);
+    }
+
+    w.writeln("hits\tcommand")?;
+    for entry in entries {
+        w.writeln(format!("{:4}\t{}", entry.hits, entry.path))?;
+    }
+
+    Ok(0)
+}
+
+impl Table {
+    /// Forgets everything once `$PATH` no longer is what the table was filled with.
+    fn sync(&mut self) {
+        let path_var = path_var();
+
+        if self.path_var != path_var {
+            self.entries.clear();
+            self.path_var = path_var;
+        }
+    }
+}
+
+fn path_var() -> String {
+    std::env::var("PATH").unwrap_or_default()
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Table> {
+    TABLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_remembers_commands() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().display().to_string();
+
+        // Not executable, so it's dropped on the next lookup.
+        hit("hash-test-tool", &path);
+        assert_eq!(lock().entries["hash-test-tool"].hits, 1);
+        assert_eq!(get("hash-test-tool"), None);
+        assert!(!lock().entries.contains_key("hash-test-tool"));
+
+        hit("hash-test-tool", "/bin/sh");
+        hit("hash-test-tool", "/bin/sh");
+        assert_eq!(lock().entries["hash-test-tool"].hits, 2);
+        assert_eq!(get("hash-test-tool"), Some("/bin/sh".into()));
+    }
+}
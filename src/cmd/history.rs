@@ -0,0 +1,63 @@
+use crate::{history, writer::Writer, Result};
+
+use std::path::PathBuf;
+
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let mut args = args.iter().map(String::as_str);
+
+    match args.next() {
+        None => print(None, w),
+        Some("-c") => {
+            history::clear();
+            Ok(0)
+        }
+        Some("-d") => match args.next().map(|n| n.parse::<i64>()) {
+            Some(Ok(num)) if history::delete(num) => Ok(0),
+            Some(_) => {
+                w.ewriteln("history: position out of range")?;
+                Ok(1)
+            }
+            None => {
+                w.ewriteln("history: -d: option requires an argument")?;
+                Ok(2)
+            }
+        },
+        Some(opt @ ("-w" | "-r" | "-a")) => {
+            let path = match args.next().map(PathBuf::from).or_else(history::file) {
+                Some(path) => path,
+                None => {
+                    w.ewriteln("history: no history file")?;
+                    return Ok(1);
+                }
+            };
+
+            let result = match opt {
+                "-w" => history::write(&path),
+                "-r" => history::read(&path),
+                _ => history::append(&path),
+            };
+
+            match result {
+                Ok(_) => Ok(0),
+                Err(err) => {
+                    w.ewriteln(format!("history: {}: {err}", path.display()))?;
+                    Ok(1)
+                }
+            }
+        }
+        Some(count) => match count.parse::<usize>() {
+            Ok(count) => print(Some(count), w),
+            Err(_) => {
+                w.ewriteln(format!("history: {count}: numeric argument required"))?;
+                Ok(2)
+            }
+        },
+    }
+}
+
+fn print(count: Option<usize>, w: &mut Writer) -> Result<i32> {
+    for (num, line) in history::entries(count) {
+        w.writeln(format!("{num:5}  {line}"))?;
+    }
+    Ok(0)
+}
@@ -0,0 +1,149 @@
+use crate::{jobs, signal, writer::Writer, Result};
+
+use libc::c_int;
+
+const USAGE: &str =
+    "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]";
+
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let mut args = args.iter().map(String::as_str).peekable();
+    let mut sig = libc::SIGTERM;
+
+    match args.peek().copied() {
+        Some("-l") | Some("-L") => {
+            args.next();
+            return list(args.collect(), w);
+        }
+        Some("-s") | Some("-n") => {
+            args.next();
+            match args.next() {
+                Some(name) => match signal_from(name) {
+                    Some(num) => sig = num,
+                    None => return invalid_signal(name, w),
+                },
+                None => return usage(w),
+            }
+        }
+        Some("--") => {
+            args.next();
+        }
+        Some(opt) if opt.len() > 1 && opt.starts_with('-') => {
+            args.next();
+            match signal_from(&opt[1..]) {
+                Some(num) => sig = num,
+                None => return invalid_signal(&opt[1..], w),
+            }
+        }
+        _ => {}
+    }
+
+    let targets: Vec<&str> = args.collect();
+    if targets.is_empty() {
+        return usage(w);
+    }
+
+    let mut status = 0;
+
+    for target in targets {
+        if let Err(msg) = send(target, sig) {
+            w.ewriteln(format!("kill: {msg}"))?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Looks a signal up like `signal::from_name`, also accepting `0`, which
+/// sends nothing and only checks that the target exists.
+fn signal_from(name: &str) -> Option<c_int> {
+    match name {
+        "0" => Some(0),
+        name => signal::from_name(name),
+    }
+}
+
+fn send(target: &str, sig: c_int) -> std::result::Result<(), String> {
+    if target.starts_with('%') {
+        let job = jobs::find(target).ok_or(format!("{target}: no such job"))?;
+        kill(-job.pgid, sig).map_err(|err| format!("{target}: {err}"))?;
+
+        // A stopped job would only see the signal once it gets to run again.
+        if job.state == jobs::State::Stopped && sig != libc::SIGCONT && sig != 0 {
+            let _ = kill(-job.pgid, libc::SIGCONT);
+        }
+        Ok(())
+    } else {
+        let pid = target
+            .parse::<i32>()
+            .map_err(|_| format!("{target}: arguments must be process or job IDs"))?;
+        kill(pid, sig).map_err(|err| format!("({pid}) - {err}"))
+    }
+}
+
+fn kill(pid: i32, sig: c_int) -> std::result::Result<(), String> {
+    if unsafe { libc::kill(pid, sig) } == 0 {
+        return Ok(());
+    }
+
+    let err = std::io::Error::last_os_error();
+    let desc = unsafe { std::ffi::CStr::from_ptr(libc::strerror(err.raw_os_error().unwrap_or(0))) };
+    Err(desc.to_string_lossy().into_owned())
+}
+
+/// `kill -l`: the whole table, or translates names to numbers and numbers
+/// (including exit statuses of signalled commands) to names.
+fn list(args: Vec<&str>, w: &mut Writer) -> Result<i32> {
+    if args.is_empty() {
+        for line in signal::table() {
+            w.writeln(line)?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+
+    for arg in args {
+        let found = match arg.parse::<c_int>() {
+            Ok(num) => {
+                let num = if num > 128 { num - 128 } else { num };
+                signal::name(num).map(str::to_string)
+            }
+            Err(_) => signal::from_name(arg).map(|num| num.to_string()),
+        };
+
+        match found {
+            Some(s) => w.writeln(s)?,
+            None => {
+                w.ewriteln(format!("kill: {arg}: invalid signal specification"))?;
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+fn invalid_signal(name: &str, w: &mut Writer) -> Result<i32> {
+    w.ewriteln(format!("kill: {name}: invalid signal specification"))?;
+    Ok(1)
+}
+
+fn usage(w: &mut Writer) -> Result<i32> {
+    w.ewriteln(USAGE)?;
+    Ok(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_checks_processes_with_signal_zero() {
+        assert_eq!(signal_from("0"), Some(0));
+        assert_eq!(signal_from("TERM"), Some(libc::SIGTERM));
+
+        assert_eq!(send(&std::process::id().to_string(), 0), Ok(()));
+        assert!(send(&i32::MAX.to_string(), 0).is_err());
+    }
+}
@@ -1,10 +1,20 @@
-use super::{writer::Writer, Result};
+use super::{complete::Strategy, jobs, writer::Writer, Error, Result};
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fmt;
 use std::io::ErrorKind;
+use std::path::Path;
 
-mod fs;
+pub mod alias;
+mod complete;
+pub mod fs;
+pub mod function;
+mod hash;
+mod history;
+mod kill;
+mod set;
+mod source;
+mod trap;
 
 #[derive(Debug, PartialEq)]
 pub struct Command {
@@ -19,6 +29,18 @@ pub enum CommandType {
     Exit,
     Pwd,
     Cd,
+    Trap,
+    Kill,
+    Hash,
+    History,
+    Set,
+    Complete,
+    Compgen,
+    Alias,
+    Unalias,
+    Source,
+    Dot,
+    Function(String),
     Empty,
     Unknown(String),
 }
@@ -29,11 +51,23 @@ impl Command {
         let cmd = tokens.next().unwrap_or_default();
 
         let r#type = match cmd.as_str() {
+            _ if function::exists(&cmd) => CommandType::Function(cmd),
             "echo" => CommandType::Echo,
             "type" => CommandType::Type,
             "exit" => CommandType::Exit,
             "pwd" => CommandType::Pwd,
             "cd" => CommandType::Cd,
+            "trap" => CommandType::Trap,
+            "kill" => CommandType::Kill,
+            "hash" => CommandType::Hash,
+            "history" => CommandType::History,
+            "set" => CommandType::Set,
+            "complete" => CommandType::Complete,
+            "compgen" => CommandType::Compgen,
+            "alias" => CommandType::Alias,
+            "unalias" => CommandType::Unalias,
+            "source" => CommandType::Source,
+            "." => CommandType::Dot,
             "" => CommandType::Empty,
             _ => CommandType::Unknown(cmd),
         };
@@ -44,54 +78,97 @@ impl Command {
         }
     }
 
-    pub fn autocomplete(s: &str) -> Vec<String> {
-        for cmd in CommandType::builtins() {
-            if cmd.to_string().starts_with(s) {
-                return vec![cmd.to_string()];
-            }
-        }
+    /// Every alias, function, builtin and `$PATH` executable whose name
+    /// matches `s` with `strategy`, best match first and otherwise sorted,
+    /// without duplicates.
+    pub fn autocomplete(s: &str, strategy: Strategy) -> Vec<String> {
+        let names: BTreeSet<String> = alias::names()
+            .into_iter()
+            .chain(function::names())
+            .chain(CommandType::builtins().map(|cmd| cmd.to_string()))
+            .chain(all_executable_names())
+            .collect();
 
-        all_executable_names()
+        let mut names: Vec<(i64, String)> = names
             .into_iter()
-            .filter(|name| name.starts_with(s))
-            .collect()
+            .filter_map(|name| Some((strategy.score(s, &name)?, name)))
+            .collect();
+        names.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        names.into_iter().map(|(_, name)| name).collect()
     }
 
-    pub fn run(self, w: &mut Writer) -> Result<()> {
+    pub fn run(self, w: &mut Writer) -> Result<i32> {
         match &self.r#type {
             CommandType::Echo => {
                 let msg = self.args.join(" ");
-                w.writeln(msg)
+                w.writeln(msg)?;
+                Ok(0)
             }
             CommandType::Type => {
+                if let Some((name, value)) = self
+                    .args
+                    .first()
+                    .and_then(|name| Some((name, alias::get(name)?)))
+                {
+                    w.writeln(format!("{name} is aliased to `{value}'"))?;
+                    return Ok(0);
+                }
+
                 let cmd = Command::new(self.args);
 
                 match cmd.r#type {
-                    CommandType::Empty => w.writeln(format!("{}: not found", cmd.r#type)),
+                    CommandType::Empty => {
+                        w.writeln(format!("{}: not found", cmd.r#type))?;
+                        Ok(1)
+                    }
+                    CommandType::Function(ref name) => {
+                        w.writeln(format!("{name} is a function"))?;
+                        Ok(0)
+                    }
                     CommandType::Unknown(ref name) => match executable(name) {
-                        Ok(Some(path)) => w.writeln(format!("{} is {path}", cmd.r#type)),
-                        Ok(None) => w.writeln(format!("{}: not found", cmd.r#type)),
-                        Err(err) => w.ewriteln(format!("{err}")),
+                        Lookup::Found(path) if hash::get(name).is_some() => {
+                            w.writeln(format!("{} is hashed ({path})", cmd.r#type))?;
+                            Ok(0)
+                        }
+                        Lookup::Found(path) => {
+                            w.writeln(format!("{} is {path}", cmd.r#type))?;
+                            Ok(0)
+                        }
+                        _ => {
+                            w.writeln(format!("{}: not found", cmd.r#type))?;
+                            Ok(1)
+                        }
                     },
-                    _ => w.writeln(format!("{} is a shell builtin", cmd.r#type)),
-                }
-            }
-            CommandType::Exit => {
-                let code = self.args.into_iter().next().unwrap_or_default();
-                match code.parse::<i32>() {
-                    Ok(code) => {
-                        std::process::exit(code);
+                    _ => {
+                        w.writeln(format!("{} is a shell builtin", cmd.r#type))?;
+                        Ok(0)
                     }
-                    Err(_) => w.ewriteln("exit code should be a number"),
                 }
             }
+            CommandType::Exit => match self.args.first() {
+                None => crate::exit(crate::vars::last_status()),
+                Some(code) => match code.parse::<i32>() {
+                    Ok(code) => crate::exit(code),
+                    Err(_) => {
+                        w.ewriteln("exit code should be a number")?;
+                        Ok(2)
+                    }
+                },
+            },
             CommandType::Pwd => {
                 let current_dir = fs::current_dir().and_then(|p| {
                     fs::path_stringify(p).ok_or(err!("Cannot stringify current directory path"))
                 });
                 match current_dir {
-                    Ok(dir) => w.writeln(dir),
-                    Err(err) => w.ewriteln(format!("{err}")),
+                    Ok(dir) => {
+                        w.writeln(dir)?;
+                        Ok(0)
+                    }
+                    Err(err) => {
+                        w.ewriteln(format!("{err}"))?;
+                        Ok(1)
+                    }
                 }
             }
             CommandType::Cd => {
@@ -108,20 +185,74 @@ impl Command {
                     } else {
                         w.ewriteln(format!("{err}"))?;
                     }
+                    return Ok(1);
                 }
-                Ok(())
+                Ok(0)
             }
-            CommandType::Empty => Ok(()),
+            CommandType::Trap => trap::run(&self.args, w),
+            CommandType::Kill => kill::run(&self.args, w),
+            CommandType::Hash => hash::run(&self.args, w),
+            CommandType::History => history::run(&self.args, w),
+            CommandType::Set => set::run(&self.args, w),
+            CommandType::Complete => complete::run(&self.args, w),
+            CommandType::Compgen => complete::compgen(&self.args, w),
+            CommandType::Alias => alias::run(&self.args, w),
+            CommandType::Unalias => alias::unalias(&self.args, w),
+            CommandType::Source | CommandType::Dot => {
+                source::run(&self.r#type.to_string(), &self.args, w)
+            }
+            CommandType::Function(name) => function::call(name, &self.args).unwrap_or_else(|| {
+                w.ewriteln(format!("{name}: command not found"))?;
+                Ok(127)
+            }),
+            CommandType::Empty => Ok(0),
             CommandType::Unknown(name) => match executable(name) {
-                Ok(Some(_)) => match run_cmd(name, &self.args) {
-                    Ok(output) => {
-                        w.write(&output.stdout)?;
-                        w.ewrite(&output.stderr)
+                Lookup::Found(path) => match run_cmd(name, &path, &self.args, w) {
+                    Ok(status) if !name.contains('/') => {
+                        hash::hit(name, &path);
+                        report(&status, w)?;
+                        Ok(status.code())
+                    }
+                    Ok(status) => {
+                        report(&status, w)?;
+                        Ok(status.code())
+                    }
+                    Err(Error::Io(err)) => match err.kind() {
+                        ErrorKind::NotFound => {
+                            w.ewriteln(format!("{name}: No such file or directory"))?;
+                            Ok(127)
+                        }
+                        ErrorKind::PermissionDenied => {
+                            w.ewriteln(format!("{name}: Permission denied"))?;
+                            Ok(126)
+                        }
+                        _ => {
+                            w.ewriteln(format!("{name}: {err}"))?;
+                            Ok(126)
+                        }
+                    },
+                    Err(err) => {
+                        w.ewriteln(format!("{name}: {err}"))?;
+                        Ok(126)
                     }
-                    Err(err) => w.ewriteln(format!("{err}")),
                 },
-                Ok(None) => w.ewriteln(format!("{name}: command not found")),
-                Err(err) => w.ewriteln(format!("{err}")),
+                Lookup::NotExecutable(_) => {
+                    w.ewriteln(format!("{name}: Permission denied"))?;
+                    Ok(126)
+                }
+                Lookup::NotFound if name.contains('/') => {
+                    if Path::new(name).is_dir() {
+                        w.ewriteln(format!("{name}: Is a directory"))?;
+                        Ok(126)
+                    } else {
+                        w.ewriteln(format!("{name}: No such file or directory"))?;
+                        Ok(127)
+                    }
+                }
+                Lookup::NotFound => {
+                    w.ewriteln(format!("{name}: command not found"))?;
+                    Ok(127)
+                }
             },
         }
     }
@@ -135,8 +266,19 @@ impl fmt::Display for CommandType {
             Self::Exit => "exit",
             Self::Pwd => "pwd",
             Self::Cd => "cd",
+            Self::Trap => "trap",
+            Self::Kill => "kill",
+            Self::Hash => "hash",
+            Self::History => "history",
+            Self::Set => "set",
+            Self::Complete => "complete",
+            Self::Compgen => "compgen",
+            Self::Alias => "alias",
+            Self::Unalias => "unalias",
+            Self::Source => "source",
+            Self::Dot => ".",
             Self::Empty => "",
-            Self::Unknown(cmd) => cmd.as_str(),
+            Self::Function(cmd) | Self::Unknown(cmd) => cmd.as_str(),
         };
         write!(f, "{str}")
     }
@@ -144,46 +286,150 @@ impl fmt::Display for CommandType {
 
 impl CommandType {
     fn builtins() -> impl Iterator<Item = Self> {
-        [Self::Echo, Self::Type, Self::Exit, Self::Pwd, Self::Cd].into_iter()
+        [
+            Self::Echo,
+            Self::Type,
+            Self::Exit,
+            Self::Pwd,
+            Self::Cd,
+            Self::Trap,
+            Self::Kill,
+            Self::Hash,
+            Self::History,
+            Self::Set,
+            Self::Complete,
+            Self::Compgen,
+            Self::Alias,
+            Self::Unalias,
+            Self::Source,
+            Self::Dot,
+        ]
+        .into_iter()
+    }
+}
+
+/// Tells the user about jobs that did not simply exit.
+fn report(status: &jobs::Status, w: &mut Writer) -> Result<()> {
+    match status {
+        jobs::Status::Stopped(job, _) => w.ewrite(format!("\n{job}\n")),
+        jobs::Status::Signaled(libc::SIGINT) => w.writeln(""),
+        _ => Ok(()),
+    }
+}
+
+/// Runs the program at `path` as `name`. Files the kernel refuses to execute
+/// for lacking a binary format or a shebang are run as scripts by this shell.
+fn run_cmd(name: &str, path: &str, args: &[String], w: &Writer) -> Result<jobs::Status> {
+    let line = std::iter::once(name)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut cmd = std::process::Command::new(path);
+    cmd.args(args)
+        .stdout(w.stdout_stdio()?)
+        .stderr(w.stderr_stdio()?);
+
+    match jobs::run_foreground(&mut cmd, &line) {
+        Err(Error::Io(err)) if err.raw_os_error() == Some(libc::ENOEXEC) => {
+            if fs::is_binary(Path::new(path))? {
+                return Err(Error::Io(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "cannot execute binary file: Exec format error",
+                )));
+            }
+
+            let mut cmd = std::process::Command::new(std::env::current_exe()?);
+            cmd.arg(path)
+                .args(args)
+                .stdout(w.stdout_stdio()?)
+                .stderr(w.stderr_stdio()?);
+
+            jobs::run_foreground(&mut cmd, &line)
+        }
+        result => result,
     }
 }
 
-fn run_cmd(name: &str, args: &[String]) -> Result<std::process::Output> {
-    let output = std::process::Command::new(name).args(args).output()?;
-    Ok(output)
+/// Outcome of resolving a command name to a program.
+#[derive(Debug, PartialEq)]
+enum Lookup {
+    Found(String),
+    /// Files by that name exist, but none the current user may execute.
+    NotExecutable(String),
+    NotFound,
 }
 
 fn all_executable_names() -> HashSet<String> {
     let path = std::env::var("PATH").unwrap_or_default();
-    fs::list_dirs(&path)
-        .into_iter()
-        .filter_map(|dir| fs::list_files(dir).ok())
-        .flatten()
-        .filter_map(|path| {
-            path.file_name()
-                .and_then(|s| s.to_str())
-                .map(|s| s.to_string())
-        })
-        .collect()
+    let dirs = fs::list_dirs(&path);
+
+    hash::names(&dirs, || {
+        dirs.iter()
+            .filter_map(|dir| fs::list_files(dir).ok())
+            .flatten()
+            .filter(|path| fs::is_executable(path))
+            .filter_map(|path| {
+                path.file_name()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string())
+            })
+            .collect()
+    })
 }
 
-fn executable(name: &str) -> Result<Option<String>> {
+/// The program a command name runs, if there is one that can be run.
+pub fn program(name: &str) -> Option<String> {
+    match executable(name) {
+        Lookup::Found(path) => Some(path),
+        _ => None,
+    }
+}
+
+/// Resolves a command name to the program it runs, from the hash table if
+/// it was run before. Names containing a `/` are paths themselves and are not
+/// looked up in `$PATH`.
+fn executable(name: &str) -> Lookup {
+    if name.contains('/') {
+        let path = Path::new(name);
+
+        return if fs::is_executable(path) {
+            Lookup::Found(name.to_string())
+        } else if path.is_file() {
+            Lookup::NotExecutable(name.to_string())
+        } else {
+            Lookup::NotFound
+        };
+    }
+
+    if let Some(path) = hash::get(name) {
+        return Lookup::Found(path);
+    }
+
     let path = std::env::var("PATH").unwrap_or_default();
     find_executable(&path, name)
 }
 
-fn find_executable(path: &str, name: &str) -> Result<Option<String>> {
+/// Finds the first file called `name` in the `path` directories that the
+/// current user may execute, following symlinks.
+fn find_executable(path: &str, name: &str) -> Lookup {
+    let mut lookup = Lookup::NotFound;
+
     for dir in fs::list_dirs(path) {
-        if let Some(p) = fs::list_files(dir)?
-            .into_iter()
-            .find(fs::filename(name))
-            .and_then(fs::path_stringify)
-        {
-            return Ok(Some(p));
+        let file = dir.join(name);
+
+        if fs::is_executable(&file) {
+            if let Some(p) = fs::path_stringify(file) {
+                return Lookup::Found(p);
+            }
+        } else if file.is_file() && lookup == Lookup::NotFound {
+            if let Some(p) = fs::path_stringify(file) {
+                lookup = Lookup::NotExecutable(p);
+            }
         }
     }
 
-    Ok(None)
+    lookup
 }
 
 #[cfg(test)]
@@ -233,12 +479,46 @@ mod tests {
         assert_eq!(cmd, expected);
     }
 
+    #[test]
+    fn it_finds_executables() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let (first, second) = (root.path().join("first"), root.path().join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+
+        for (dir, mode) in [(&first, 0o644), (&second, 0o755)] {
+            let file = dir.join("tool");
+            std::fs::write(&file, "").unwrap();
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let path = format!("{}:{}", first.display(), second.display());
+        let expected = Lookup::Found(format!("{}/tool", second.display()));
+        assert_eq!(find_executable(&path, "tool"), expected);
+
+        let path = first.display().to_string();
+        let expected = Lookup::NotExecutable(format!("{}/tool", first.display()));
+        assert_eq!(find_executable(&path, "tool"), expected);
+
+        assert_eq!(find_executable(&path, "missing"), Lookup::NotFound);
+    }
+
     #[test]
     fn it_completes_the_command() {
-        let subject = Command::autocomplete("ech");
+        let subject = Command::autocomplete("ech", Strategy::Prefix);
         assert_eq!(subject, vec!["echo".to_string()]);
 
-        let subject = Command::autocomplete("exi");
+        let subject = Command::autocomplete("exi", Strategy::Prefix);
         assert_eq!(subject, vec!["exit".to_string()]);
+
+        let subject = Command::autocomplete("e", Strategy::Prefix);
+        assert!(subject.contains(&"echo".to_string()));
+        assert!(subject.contains(&"exit".to_string()));
+        assert!(subject.windows(2).all(|w| w[0] < w[1]));
+
+        let subject = Command::autocomplete("hstry", Strategy::Fuzzy);
+        assert!(subject.contains(&"history".to_string()));
     }
 }
@@ -0,0 +1,80 @@
+use crate::{editor, vars, writer::Writer, Result};
+
+const OPTIONS: [&str; 3] = ["emacs", "menucomplete", "vi"];
+
+/// `set -o name` / `set +o name` turn an option on or off, `set -o` and
+/// `set +o` list them, and any other arguments, or those after `--`, become
+/// the positional parameters.
+///
+/// The line editor always uses one of its two modes, so turning either
+/// `emacs` or `vi` off selects the other.
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let mut args = args.iter().map(String::as_str).peekable();
+
+    match args.peek().copied() {
+        None => {
+            let mut vars: Vec<(String, String)> = std::env::vars().collect();
+            vars.sort();
+
+            for (name, value) in vars {
+                w.writeln(format!("{name}={value}"))?;
+            }
+            Ok(0)
+        }
+        Some(flag @ ("-o" | "+o")) => {
+            args.next();
+            let on = flag == "-o";
+
+            let Some(name) = args.next() else {
+                return print(on, w);
+            };
+
+            match name {
+                "vi" => editor::set_vi(on),
+                "emacs" => editor::set_vi(!on),
+                "menucomplete" => editor::set_menu_complete(on),
+                _ => {
+                    w.ewriteln(format!("set: {name}: invalid option name"))?;
+                    return Ok(1);
+                }
+            }
+            Ok(0)
+        }
+        Some("--") => {
+            args.next();
+            vars::set_positional(args.map(String::from).collect());
+            Ok(0)
+        }
+        Some(opt) if opt.len() > 1 && (opt.starts_with('-') || opt.starts_with('+')) => {
+            w.ewriteln(format!("set: {opt}: invalid option"))?;
+            w.ewriteln("set: usage: set [-o option-name] [+o option-name] [--] [arg ...]")?;
+            Ok(2)
+        }
+        Some(_) => {
+            vars::set_positional(args.map(String::from).collect());
+            Ok(0)
+        }
+    }
+}
+
+/// Lists the options as a table for `set -o`, or as the commands that
+/// restore them for `set +o`.
+fn print(table: bool, w: &mut Writer) -> Result<i32> {
+    for name in OPTIONS {
+        let on = match name {
+            "vi" => editor::is_vi(),
+            "emacs" => !editor::is_vi(),
+            _ => editor::is_menu_complete(),
+        };
+
+        if table {
+            let state = if on { "on" } else { "off" };
+            w.writeln(format!("{name:<15}\t{state}"))?;
+        } else {
+            let flag = if on { '-' } else { '+' };
+            w.writeln(format!("set {flag}o {name}"))?;
+        }
+    }
+
+    Ok(0)
+}
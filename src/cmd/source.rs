@@ -0,0 +1,111 @@
+use super::fs;
+use crate::{trap, vars, writer::Writer, Result};
+
+use std::path::PathBuf;
+
+/// `source FILE [args ...]`, or `. FILE [args ...]`, runs the commands in
+/// `FILE` in this shell, so that what they change stays changed. With args,
+/// they are the positional parameters while it runs.
+pub fn run(name: &str, args: &[String], w: &mut Writer) -> Result<i32> {
+    let Some((file, args)) = args.split_first() else {
+        w.ewriteln(format!("{name}: filename argument required"))?;
+        w.ewriteln(format!("{name}: usage: {name} filename [arguments]"))?;
+        return Ok(2);
+    };
+
+    let Some(path) = find(&std::env::var("PATH").unwrap_or_default(), file) else {
+        w.ewriteln(format!("{name}: {file}: No such file or directory"))?;
+        return Ok(1);
+    };
+
+    let outer = (!args.is_empty()).then(|| vars::set_positional(args.to_vec()));
+    let result = crate::source(&path);
+    if let Some(outer) = outer {
+        vars::set_positional(outer);
+    }
+
+    let status = match result {
+        Ok(status) => status,
+        Err(err) => {
+            w.ewriteln(format!("{name}: {file}: {err}"))?;
+            1
+        }
+    };
+
+    vars::set_last_status(status);
+    trap::run(trap::Condition::Return)?;
+    Ok(status)
+}
+
+/// Looks `file` up in the directories of `path_var` when it has no slash,
+/// falling back on the current directory, as bash does.
+fn find(path_var: &str, file: &str) -> Option<PathBuf> {
+    if !file.contains('/') {
+        let found = fs::list_dirs(path_var)
+            .into_iter()
+            .map(|dir| dir.join(file))
+            .find(|path| path.is_file());
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    let path = PathBuf::from(file);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::alias;
+
+    #[test]
+    fn it_finds_files_in_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::write(root.join("rc"), "").unwrap();
+
+        let path_var = root.display().to_string();
+        assert_eq!(find(&path_var, "rc"), Some(root.join("rc")));
+        assert_eq!(find(&path_var, "dir"), None);
+        assert_eq!(find(&path_var, "missing"), None);
+
+        let file = root.join("rc").display().to_string();
+        assert_eq!(find("", &file), Some(root.join("rc")));
+    }
+
+    #[test]
+    fn it_runs_files_in_this_shell() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().canonicalize().unwrap();
+        let rc = dir.join("rc");
+        let text = format!(
+            "cd {}\n\
+             alias source-test-ll='ls -l'\n\
+             echo $1 $# > args\n\
+             type source-test-missing > type\n",
+            dir.display()
+        );
+        std::fs::write(&rc, text).unwrap();
+
+        let _state = vars::test_lock();
+        let cwd = std::env::current_dir().unwrap();
+        let (outer, outer_status) = (vars::positional(), vars::last_status());
+        vars::set_positional(vec!["outer".into()]);
+
+        let mut w = Writer::builder().build().unwrap();
+        let args = [rc.display().to_string(), "a".into(), "b".into()];
+        let status = run("source", &args, &mut w).unwrap();
+        let moved_to = std::env::current_dir().unwrap();
+        let positional = vars::set_positional(outer);
+        std::env::set_current_dir(cwd).unwrap();
+        vars::set_last_status(outer_status);
+
+        assert_eq!(status, 1);
+        assert_eq!(moved_to, dir);
+        assert_eq!(alias::get("source-test-ll"), Some("ls -l".into()));
+        assert_eq!(std::fs::read_to_string(dir.join("args")).unwrap(), "a 2\n");
+        assert_eq!(positional, vec!["outer"]);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::trap::{self, Action, Condition};
+use crate::{signal, writer::Writer, Result};
+
+pub fn run(args: &[String], w: &mut Writer) -> Result<i32> {
+    let mut args = args.iter().map(String::as_str).peekable();
+
+    match args.peek() {
+        None => return print(&[], w),
+        Some(&"-l") => return list_signals(w),
+        Some(&"-p") => {
+            args.next();
+            return print(&args.collect::<Vec<_>>(), w);
+        }
+        Some(&"--") => {
+            args.next();
+        }
+        _ => {}
+    }
+
+    let args: Vec<&str> = args.collect();
+    let (action, conds) = match args.as_slice() {
+        [] => return print(&[], w),
+        [cond] => (Action::Default, vec![*cond]),
+        ["-", conds @ ..] => (Action::Default, conds.to_vec()),
+        ["", conds @ ..] => (Action::Ignore, conds.to_vec()),
+        [cmd, conds @ ..] => (Action::Command(cmd.to_string()), conds.to_vec()),
+    };
+
+    let mut status = 0;
+
+    for name in conds {
+        match Condition::parse(name) {
+            Some(Condition::Signal(libc::SIGKILL | libc::SIGSTOP)) => {
+                w.ewriteln(format!("trap: {name}: cannot trap"))?;
+                status = 1;
+            }
+            Some(cond) => trap::set(cond, action.clone()),
+            None => {
+                w.ewriteln(format!("trap: {name}: invalid signal specification"))?;
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+fn print(names: &[&str], w: &mut Writer) -> Result<i32> {
+    let mut status = 0;
+
+    let traps = if names.is_empty() {
+        trap::list()
+    } else {
+        let mut traps = vec![];
+        for name in names {
+            match Condition::parse(name) {
+                Some(cond) => {
+                    if let Some(action) = trap::get(cond) {
+                        traps.push((cond, action));
+                    }
+                }
+                None => {
+                    w.ewriteln(format!("trap: {name}: invalid signal specification"))?;
+                    status = 1;
+                }
+            }
+        }
+        traps
+    };
+
+    for (cond, action) in traps {
+        w.writeln(format!(
+            "trap -- '{}' {cond}",
+            action.replace('\'', "'\\''")
+        ))?;
+    }
+
+    Ok(status)
+}
+
+fn list_signals(w: &mut Writer) -> Result<i32> {
+    for line in signal::table() {
+        w.writeln(line)?;
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_refuses_to_trap_kill_and_stop() {
+        let stderr = tempfile::NamedTempFile::new().unwrap();
+        let mut w = Writer::builder()
+            .stderr_new(&stderr.path())
+            .build()
+            .unwrap();
+        let args: Vec<String> = ["echo caught", "KILL", "SIGSTOP"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(run(&args, &mut w).unwrap(), 1);
+        assert_eq!(trap::get(Condition::Signal(libc::SIGKILL)), None);
+        assert_eq!(trap::get(Condition::Signal(libc::SIGSTOP)), None);
+
+        drop(w);
+        assert_eq!(
+            std::fs::read_to_string(stderr.path()).unwrap(),
+            "trap: KILL: cannot trap\ntrap: SIGSTOP: cannot trap\n"
+        );
+    }
+}
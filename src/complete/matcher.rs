@@ -0,0 +1,145 @@
+/// How much a fuzzy match gets for each character, and on top of that for
+/// one starting the name or a word in it, or following the previous match.
+const MATCH: i64 = 16;
+const START: i64 = 24;
+const BOUNDARY: i64 = 16;
+const CONSECUTIVE: i64 = 12;
+
+/// How the typed word is matched against the names it may complete to.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Names starting with the word.
+    #[default]
+    Prefix,
+    /// Names starting with the word in any case.
+    IgnoreCase,
+    /// Names containing the word, earlier being better.
+    Substring,
+    /// Names containing the characters of the word in order, better the
+    /// more of them start words or follow each other, as `gco` does in
+    /// `git-checkout-obsolete`.
+    Fuzzy,
+}
+
+/// The strategies to try in turn until one finds something: the words of
+/// `$COMPLETION_MATCH`, out of `prefix`, `ignore-case`, `substring` and
+/// `fuzzy`, or just `prefix`.
+pub fn strategies() -> Vec<Strategy> {
+    let names = std::env::var("COMPLETION_MATCH").unwrap_or_default();
+    let strategies: Vec<Strategy> = names
+        .split_whitespace()
+        .filter_map(|name| match name {
+            "prefix" => Some(Strategy::Prefix),
+            "ignore-case" => Some(Strategy::IgnoreCase),
+            "substring" => Some(Strategy::Substring),
+            "fuzzy" => Some(Strategy::Fuzzy),
+            _ => None,
+        })
+        .collect();
+
+    if strategies.is_empty() {
+        vec![Strategy::Prefix]
+    } else {
+        strategies
+    }
+}
+
+impl Strategy {
+    /// How well `name` matches `pattern`, higher being better, or `None`
+    /// when it doesn't. Substring and fuzzy matches ignore case unless
+    /// `pattern` has capitals in it.
+    pub fn score(self, pattern: &str, name: &str) -> Option<i64> {
+        let fold = !pattern.chars().any(char::is_uppercase);
+
+        match self {
+            Strategy::Prefix => name.starts_with(pattern).then_some(0),
+            Strategy::IgnoreCase => name
+                .to_lowercase()
+                .starts_with(&pattern.to_lowercase())
+                .then_some(0),
+            Strategy::Substring if fold => {
+                let i = name.to_lowercase().find(&pattern.to_lowercase())?;
+                Some(-(i as i64))
+            }
+            Strategy::Substring => name.find(pattern).map(|i| -(i as i64)),
+            Strategy::Fuzzy => fuzzy(pattern, name, fold),
+        }
+    }
+}
+
+/// Scores the best way to match the characters of `pattern` in order in
+/// `name`, each skipped character costing a point. `best[j]` holds the best
+/// score with the current character of `pattern` matched at `name[j]`.
+fn fuzzy(pattern: &str, name: &str, fold: bool) -> Option<i64> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let eq = |a: char, b: char| a == b || (fold && a.to_lowercase().eq(b.to_lowercase()));
+    let name: Vec<char> = name.chars().collect();
+    let bonus = |j: usize| match j {
+        0 => START,
+        j if !name[j - 1].is_alphanumeric() => BOUNDARY,
+        j if name[j - 1].is_lowercase() && name[j].is_uppercase() => BOUNDARY,
+        _ => 0,
+    };
+
+    let mut best: Vec<Option<i64>> = (0..name.len()).map(|j| Some(-(j as i64))).collect();
+
+    for (i, p) in pattern.chars().enumerate() {
+        let mut next = vec![None; name.len()];
+        // The best of `best[k] + k` for the `k` before `j`, to take the
+        // cost of the gap from `k` to `j` off.
+        let mut before: Option<i64> = None;
+
+        for j in 0..name.len() {
+            if eq(p, name[j]) {
+                let from = if i == 0 {
+                    best[j]
+                } else {
+                    let gap = before.map(|b| b - j as i64 + 1);
+                    let next_to = match j {
+                        0 => None,
+                        j => best[j - 1].map(|s| s + CONSECUTIVE),
+                    };
+                    gap.max(next_to)
+                };
+                next[j] = from.map(|s| s + MATCH + bonus(j));
+            }
+
+            if i > 0 {
+                before = before.max(best[j].map(|s| s + j as i64));
+            }
+        }
+
+        best = next;
+    }
+
+    best.into_iter().flatten().max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_with_each_strategy() {
+        assert_eq!(Strategy::Prefix.score("gi", "git"), Some(0));
+        assert_eq!(Strategy::Prefix.score("Gi", "git"), None);
+        assert_eq!(Strategy::IgnoreCase.score("read", "README.md"), Some(0));
+        assert_eq!(Strategy::Substring.score("me", "README.md"), Some(-4));
+        assert_eq!(Strategy::Substring.score("Me", "README.md"), None);
+        assert_eq!(Strategy::Fuzzy.score("gxo", "git-checkout"), None);
+        assert_eq!(Strategy::Fuzzy.score("", "git"), Some(0));
+    }
+
+    #[test]
+    fn it_ranks_fuzzy_matches() {
+        let score = |name| Strategy::Fuzzy.score("gco", name).unwrap();
+
+        assert!(score("git-checkout-obsolete") > score("gecko"));
+        assert!(score("gco") > score("git-checkout-obsolete"));
+        assert!(score("gitCheckOut") > score("gecko"));
+        assert_eq!(score("git-checkout-obsolete"), 93);
+    }
+}
@@ -0,0 +1,405 @@
+mod matcher;
+mod names;
+mod path;
+mod spec;
+
+pub use matcher::Strategy;
+pub use spec::{Context, Spec};
+
+use crate::cmd::{fs, Command};
+use crate::parser;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+static SPECS: Mutex<BTreeMap<String, Spec>> = Mutex::new(BTreeMap::new());
+
+/// Characters that have to be escaped for a completed word to be read back
+/// as the same single word. A `}` means nothing without a `{` before it.
+const SPECIAL: &str = " \t\n\\'\"$`&|;<>()*?[]{!#";
+/// The ones that still have to be inside double quotes.
+const SPECIAL_QUOTED: &str = "\\\"$`";
+
+/// A word Tab can complete the current one with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The whole word, unescaped.
+    pub word: String,
+    /// How the candidate is listed: file names without their directory.
+    pub display: String,
+    /// Whether a space follows the word once it is complete. Directories
+    /// get none, so that completion can go on inside them.
+    pub space: bool,
+    /// How well the word matched what was typed, higher being better.
+    pub score: i64,
+}
+
+impl Candidate {
+    fn new(word: String) -> Self {
+        Self {
+            display: word.clone(),
+            word,
+            space: true,
+            score: 0,
+        }
+    }
+
+    /// The candidate `word`, if it matches `pattern`.
+    fn matching(word: String, pattern: &str, strategy: Strategy) -> Option<Self> {
+        let score = strategy.score(pattern, &word)?;
+        Some(Self {
+            score,
+            ..Self::new(word)
+        })
+    }
+}
+
+/// The candidates for the word under the cursor.
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    /// Where the word starts and ends in the line. Both are the cursor when
+    /// it isn't on a word.
+    pub start: usize,
+    pub end: usize,
+    /// The word as far as the cursor, with its quotes and escapes removed.
+    pub word: String,
+    /// The quote still open at the cursor, if any.
+    pub quote: Option<char>,
+    pub candidates: Vec<Candidate>,
+}
+
+/// Completes the word under the cursor from its start up to the cursor: a
+/// command name in the first word, and in the others whatever `complete` set
+/// up for the command, or a file or directory. A first word with a `/` in it
+/// is completed as a path to an executable.
+///
+/// Variables after `$` or `${`, users after a leading `~` and hosts after
+/// `@` are completed in any word, replacing just the name.
+///
+/// The word is matched with each of the `matcher::strategies` in turn until
+/// one finds something, and the candidates come best match first.
+pub fn complete(line: &str, cursor: usize) -> Completion {
+    let mut strategies = matcher::strategies().into_iter();
+    let mut completion = complete_with(line, cursor, strategies.next().unwrap_or_default());
+
+    for strategy in strategies {
+        if !completion.candidates.is_empty() {
+            break;
+        }
+        completion = complete_with(line, cursor, strategy);
+    }
+
+    completion
+}
+
+fn complete_with(line: &str, cursor: usize, strategy: Strategy) -> Completion {
+    let tokens = parser::tokens(line);
+    let cword = tokens.iter().take_while(|t| t.span.end < cursor).count();
+
+    let (span, typed) = match tokens.get(cword) {
+        Some(token) if token.span.start < cursor => {
+            let typed = parser::tokens(&line[token.span.start..cursor]);
+            (
+                token.span.clone(),
+                typed.into_iter().next().unwrap_or_default(),
+            )
+        }
+        _ => (cursor..cursor, parser::Token::default()),
+    };
+
+    let typed_raw = &line[span.start..cursor];
+    let rest = &line[cursor..span.end];
+    if let Some((start, end, mut candidates)) = names(typed_raw, rest, cword, typed.quote, strategy)
+    {
+        rank(&mut candidates);
+        return Completion {
+            start: span.start + start,
+            end: cursor + end,
+            word: typed_raw[start..].to_string(),
+            quote: None,
+            candidates,
+        };
+    }
+
+    let mut words: Vec<String> = tokens.into_iter().map(|t| t.text).collect();
+    if span.is_empty() {
+        words.insert(cword, typed.text);
+    } else {
+        words[cword] = typed.text;
+    }
+
+    let ctx = Context {
+        line,
+        point: cursor,
+        words,
+        cword,
+        strategy,
+    };
+    let word = ctx.word().to_string();
+
+    let mut candidates = if ctx.cword > 0 {
+        match find(&ctx.words[0]) {
+            Some(spec) => spec.generate(&ctx),
+            None => path::complete(&word, strategy, |_| true),
+        }
+    } else if word.contains('/') {
+        path::complete(&word, strategy, |path| {
+            path.is_dir() || fs::is_executable(path)
+        })
+    } else {
+        Command::autocomplete(&word, strategy)
+            .into_iter()
+            .filter_map(|name| Candidate::matching(name, &word, strategy))
+            .collect()
+    };
+    rank(&mut candidates);
+
+    Completion {
+        start: span.start,
+        end: span.end,
+        word,
+        quote: typed.quote,
+        candidates,
+    }
+}
+
+/// Completes the name `typed` ends with, if it's a variable, user or host,
+/// given the rest of the word after the cursor. Returns where the name
+/// starts in `typed` and ends in `rest`, and the candidates, which get no
+/// space after them inside quotes.
+fn names(
+    typed: &str,
+    rest: &str,
+    cword: usize,
+    quote: Option<char>,
+    strategy: Strategy,
+) -> Option<(usize, usize, Vec<Candidate>)> {
+    let space = quote.is_none();
+    let len = |rest: &str, name: fn(char) -> bool| rest.find(|c| !name(c)).unwrap_or(rest.len());
+
+    let dollar = typed
+        .rfind('$')
+        .filter(|&i| quote != Some('\'') && !typed[..i].ends_with('\\'));
+    if let Some(i) = dollar {
+        let braced = typed[i + 1..].starts_with('{');
+        let start = if braced { i + 2 } else { i + 1 };
+        let prefix = &typed[start..];
+
+        if prefix.chars().all(is_variable_char) {
+            let mut end = len(rest, is_variable_char);
+            if braced && rest[end..].starts_with('}') {
+                end += 1;
+            }
+
+            let close = if braced { "}" } else { "" };
+            let candidates = names::variables()
+                .into_iter()
+                .filter_map(|name| Candidate::matching(name, prefix, strategy))
+                .map(|c| Candidate {
+                    word: format!("{}{close}", c.word),
+                    display: format!("{}{}{close}", &typed[i..start], c.word),
+                    space,
+                    ..c
+                })
+                .collect();
+            return Some((start, end, candidates));
+        }
+    }
+
+    if let Some(prefix) = typed.strip_prefix('~').filter(|p| !p.contains('/')) {
+        let candidates = names::users()
+            .into_iter()
+            .filter_map(|user| Candidate::matching(user, prefix, strategy))
+            .map(|c| Candidate {
+                word: format!("{}/", c.word),
+                display: format!("~{}", c.word),
+                space: false,
+                ..c
+            })
+            .collect();
+        return Some((1, len(rest, |c| c != '/'), candidates));
+    }
+
+    let at = typed.rfind('@').filter(|_| cword > 0);
+    if let Some(i) = at.filter(|&i| !typed[i + 1..].contains([':', '/'])) {
+        let candidates = names::hosts()
+            .into_iter()
+            .filter_map(|host| Candidate::matching(host, &typed[i + 1..], strategy))
+            .map(|c| Candidate { space, ..c })
+            .collect();
+        return Some((i + 1, len(rest, |c| !matches!(c, ':' | '/')), candidates));
+    }
+
+    None
+}
+
+/// Puts the best matches first, and those that match as well in order.
+fn rank(candidates: &mut [Candidate]) {
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.word.cmp(&b.word)));
+}
+
+fn is_variable_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// The spec `complete` set up for `name`, or for its file name when it's
+/// run by path.
+fn find(name: &str) -> Option<Spec> {
+    let specs = lock();
+    let base = name.rsplit('/').next().unwrap_or(name);
+    specs.get(name).or_else(|| specs.get(base)).cloned()
+}
+
+pub fn set(name: &str, spec: Spec) {
+    lock().insert(name.to_string(), spec);
+}
+
+/// Forgets the spec for `name`, or every spec without a name.
+pub fn remove(name: Option<&str>) -> bool {
+    match name {
+        Some(name) => lock().remove(name).is_some(),
+        None => {
+            lock().clear();
+            true
+        }
+    }
+}
+
+/// The specs for `names`, or all of them without any, along with the names
+/// that have none.
+pub fn specs(names: &[String]) -> (Vec<(String, Spec)>, Vec<String>) {
+    let specs = lock();
+
+    if names.is_empty() {
+        let all = specs.iter().map(|(n, s)| (n.clone(), s.clone())).collect();
+        return (all, vec![]);
+    }
+
+    let (found, missing): (Vec<&String>, Vec<&String>) =
+        names.iter().partition(|n| specs.contains_key(*n));
+    let found = found
+        .into_iter()
+        .map(|n| (n.clone(), specs[n].clone()))
+        .collect();
+    (found, missing.into_iter().cloned().collect())
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<String, Spec>> {
+    SPECS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Writes `word` back the way it was being typed: inside `quote` if one
+/// was open, closing it again when `close` is set, or else with the
+/// characters the parser would take as quotes, separators or expansions
+/// escaped. `~` is left alone so that a leading one still stands for the
+/// home directory.
+pub fn escape(word: &str, quote: Option<char>, close: bool) -> String {
+    let (quote, special) = match quote {
+        Some('\'') if !word.contains('\'') => ("'", ""),
+        Some('"') => ("\"", SPECIAL_QUOTED),
+        _ => ("", SPECIAL),
+    };
+
+    let mut escaped = quote.to_string();
+    for c in word.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    if close {
+        escaped.push_str(quote);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_completes_the_word_under_the_cursor() {
+        let words = |c: &Completion| -> Vec<String> {
+            c.candidates.iter().map(|c| c.word.clone()).collect()
+        };
+
+        let completion = complete("ec foo", 2);
+        assert_eq!((completion.start, completion.end), (0, 2));
+        assert_eq!(words(&completion), vec!["echo"]);
+
+        let completion = complete("echo | ty", 1);
+        assert_eq!(
+            (completion.start, completion.end, completion.word.as_str()),
+            (0, 4, "e")
+        );
+
+        let completion = complete("cat  foo", 4);
+        assert_eq!((completion.start, completion.end), (4, 4));
+
+        std::env::set_var("COMPLETE_TEST_VAR", "1");
+        let completion = complete("echo a${COMPLETE_TEST_V}/b", 23);
+        assert_eq!((completion.start, completion.end), (8, 24));
+        assert_eq!(words(&completion), vec!["COMPLETE_TEST_VAR}"]);
+
+        if let Some(user) = crate::cmd::fs::current_user() {
+            let line = format!("ls ~{user}");
+            let completion = complete(&line, line.len());
+            assert!(words(&completion).contains(&format!("{user}/")));
+            assert_eq!(completion.start, 4);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("complete")).unwrap();
+        let line = format!("cat \"{}/co", dir.path().display());
+        let completion = complete(&line, line.len());
+        assert_eq!(completion.word, format!("{}/co", dir.path().display()));
+        assert_eq!(completion.quote, Some('"'));
+        assert_eq!(
+            words(&completion),
+            vec![format!("{}/complete/", dir.path().display())]
+        );
+    }
+
+    #[test]
+    fn it_completes_with_specs() {
+        set(
+            "complete-test",
+            Spec {
+                words: Some("start stop".into()),
+                ..Spec::default()
+            },
+        );
+
+        let completion = complete("./complete-test s x", 17);
+        let words: Vec<&str> = completion
+            .candidates
+            .iter()
+            .map(|c| c.word.as_str())
+            .collect();
+        assert_eq!(words, vec!["start", "stop"]);
+        assert_eq!(completion.start, 16);
+
+        assert!(remove(Some("complete-test")));
+        assert!(!remove(Some("complete-test")));
+    }
+
+    #[test]
+    fn it_escapes_words() {
+        assert_eq!(
+            escape("my file (1).txt", None, true),
+            "my\\ file\\ \\(1\\).txt"
+        );
+        assert_eq!(escape("~/a&b", None, true), "~/a\\&b");
+        assert_eq!(escape("my $x", Some('"'), true), "\"my \\$x\"");
+        assert_eq!(escape("src dir/", Some('\''), false), "'src dir/");
+        assert_eq!(escape("it's", Some('\''), true), "it\\'s");
+    }
+
+    #[test]
+    fn it_completes_commands() {
+        let completion = complete("ech", 3);
+        assert_eq!(completion.start, 0);
+        assert_eq!(completion.candidates, vec![Candidate::new("echo".into())]);
+    }
+}
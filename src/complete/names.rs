@@ -0,0 +1,137 @@
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+/// The names of the variables.
+pub fn variables() -> Vec<String> {
+    std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .collect()
+}
+
+/// The users in the passwd database.
+pub fn users() -> Vec<String> {
+    let mut users = vec![];
+
+    unsafe {
+        libc::setpwent();
+        loop {
+            let entry = libc::getpwent();
+            if entry.is_null() {
+                break;
+            }
+
+            let name = CStr::from_ptr((*entry).pw_name);
+            users.push(name.to_string_lossy().into_owned());
+        }
+        libc::endpwent();
+    }
+
+    users.sort();
+    users.dedup();
+    users
+}
+
+/// The hosts in `/etc/hosts`, `~/.ssh/known_hosts` and `~/.ssh/config`.
+pub fn hosts() -> Vec<String> {
+    let ssh = PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".ssh");
+    let read = |path: PathBuf| std::fs::read_to_string(path).unwrap_or_default();
+
+    let mut hosts = etc_hosts(&read("/etc/hosts".into()));
+    hosts.extend(known_hosts(&read(ssh.join("known_hosts"))));
+    hosts.extend(ssh_config(&read(ssh.join("config"))));
+
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+/// The names after the address on each line.
+fn etc_hosts(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split_whitespace().skip(1))
+        .map(String::from)
+        .collect()
+}
+
+/// The hosts of the first field, or the second after a `@cert-authority` or
+/// `@revoked` marker. Hashed hosts can't be read back and are skipped, and
+/// `[host]:port` gives the host.
+fn known_hosts(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next()? {
+                marker if marker.starts_with('@') => fields.next(),
+                field => Some(field),
+            }
+        })
+        .flat_map(|field| field.split(','))
+        .filter(|host| !host.starts_with(['|', '#']))
+        .map(|host| match host.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or(rest),
+            None => host,
+        })
+        .filter(|host| !is_pattern(host))
+        .map(String::from)
+        .collect()
+}
+
+/// The names on `Host` lines and the values of `HostName`, leaving out
+/// patterns.
+fn ssh_config(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once([' ', '\t', '='])?;
+            let key = key.to_ascii_lowercase();
+            (key == "host" || key == "hostname").then_some(value)
+        })
+        .flat_map(|value| {
+            value
+                .trim_start_matches([' ', '\t', '='])
+                .split_whitespace()
+        })
+        .filter(|host| !is_pattern(host))
+        .map(String::from)
+        .collect()
+}
+
+fn is_pattern(host: &str) -> bool {
+    host.contains(['*', '?', '!'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_hosts() {
+        let text = "127.0.0.1 localhost\n# 10.0.0.1 old\n::1 ip6-localhost ip6-loopback # v6\n";
+        assert_eq!(
+            etc_hosts(text),
+            vec!["localhost", "ip6-localhost", "ip6-loopback"]
+        );
+
+        let text = "github.com,140.82.121.4 ssh-ed25519 AAAA\n\
+                    |1|abc= ssh-rsa AAAA\n\
+                    [git.example.org]:2222 ssh-rsa AAAA\n\
+                    @cert-authority *.example.com ssh-rsa AAAA\n";
+        assert_eq!(
+            known_hosts(text),
+            vec!["github.com", "140.82.121.4", "git.example.org"]
+        );
+
+        let text = "Host work *.internal\n  HostName work.example.com\n  User me\nhost=box\n";
+        assert_eq!(ssh_config(text), vec!["work", "work.example.com", "box"]);
+    }
+
+    #[test]
+    fn it_finds_users_and_variables() {
+        if let Some(user) = crate::cmd::fs::current_user() {
+            assert!(users().contains(&user));
+        }
+
+        std::env::set_var("NAMES_TEST_VAR", "1");
+        assert!(variables().contains(&"NAMES_TEST_VAR".to_string()));
+    }
+}
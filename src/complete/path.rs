@@ -0,0 +1,106 @@
+use super::{Candidate, Strategy};
+use crate::cmd::fs;
+
+use std::path::{Path, PathBuf};
+
+/// Completes `word` as a path: the entries of the directory it names, up to
+/// its last `/`, that match the rest of it and pass `filter`. Hidden entries
+/// are only offered once the rest starts with a `.`.
+pub fn complete(word: &str, strategy: Strategy, filter: impl Fn(&Path) -> bool) -> Vec<Candidate> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+
+    let Ok(entries) = std::fs::read_dir(expand(dir)) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !prefix.starts_with('.') {
+                return None;
+            }
+            let score = strategy.score(prefix, &name)?;
+
+            let path = entry.path();
+            if !filter(&path) {
+                return None;
+            }
+
+            let slash = if path.is_dir() { "/" } else { "" };
+            Some(Candidate {
+                word: format!("{dir}{name}{slash}"),
+                display: format!("{name}{slash}"),
+                space: slash.is_empty(),
+                score,
+            })
+        })
+        .collect()
+}
+
+/// The directory to list for `dir`, the part of a word up to its last `/`,
+/// with a `~` or `~user` starting it expanded.
+fn expand(dir: &str) -> PathBuf {
+    if dir.is_empty() {
+        return PathBuf::from(".");
+    }
+
+    let home = dir
+        .strip_prefix('~')
+        .and_then(|rest| rest.split_once('/'))
+        .and_then(|(user, rest)| Some((fs::home_dir(user)?, rest)));
+
+    match home {
+        Some((home, rest)) => PathBuf::from(home).join(rest),
+        None => PathBuf::from(dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_expands_home_directories() {
+        if let Some(user) = fs::current_user() {
+            let home = PathBuf::from(fs::home_dir(&user).unwrap());
+            assert_eq!(expand(&format!("~{user}/")), home);
+            assert_eq!(expand(&format!("~{user}/src/")), home.join("src"));
+        }
+        assert_eq!(expand("~no-such-user/"), PathBuf::from("~no-such-user/"));
+        assert_eq!(expand(""), PathBuf::from("."));
+    }
+
+    #[test]
+    fn it_completes_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+        std::fs::write(dir.join("some file"), "").unwrap();
+        std::fs::write(dir.join(".secret"), "").unwrap();
+        std::fs::write(dir.join("other"), "").unwrap();
+
+        let word = format!("{}/s", dir.display());
+        let mut candidates = complete(&word, Strategy::Prefix, |_| true);
+        candidates.sort_by(|a, b| a.word.cmp(&b.word));
+
+        let names: Vec<(&str, bool)> = candidates
+            .iter()
+            .map(|c| (c.display.as_str(), c.space))
+            .collect();
+        assert_eq!(names, vec![("some file", true), ("sub dir/", false)]);
+        assert_eq!(candidates[1].word, format!("{}/sub dir/", dir.display()));
+
+        let word = format!("{}/.", dir.display());
+        assert_eq!(complete(&word, Strategy::Prefix, |_| true).len(), 1);
+
+        let word = format!("{}/", dir.display());
+        assert_eq!(
+            complete(&word, Strategy::Prefix, |path| path.is_dir()).len(),
+            1
+        );
+    }
+}
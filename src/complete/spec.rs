@@ -0,0 +1,307 @@
+use super::{path, rank, Candidate, Strategy};
+use crate::cmd::{function, Command};
+use crate::vars;
+
+use std::ffi::CString;
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+
+/// How the arguments of a command are completed, as set up with `complete`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spec {
+    /// `-d`
+    pub dirs: bool,
+    /// `-f`
+    pub files: bool,
+    /// `-c`
+    pub commands: bool,
+    /// `-W`, split on whitespace when completing.
+    pub words: Option<String>,
+    /// `-F`
+    pub function: Option<String>,
+    /// `-C`
+    pub command: Option<String>,
+    /// `-X`: candidates matching this pattern are dropped, or all others
+    /// when it starts with `!`. A `&` in it stands for the word.
+    pub filter: Option<String>,
+}
+
+/// The line being completed, split into words.
+#[derive(Debug, PartialEq)]
+pub struct Context<'a> {
+    pub line: &'a str,
+    /// Where the cursor is in `line`.
+    pub point: usize,
+    pub words: Vec<String>,
+    /// The index of the word being completed.
+    pub cword: usize,
+    /// How the word is matched against the candidates.
+    pub strategy: Strategy,
+}
+
+impl Context<'_> {
+    pub fn word(&self) -> &str {
+        self.words.get(self.cword).map_or("", String::as_str)
+    }
+}
+
+impl Spec {
+    pub fn generate(&self, ctx: &Context) -> Vec<Candidate> {
+        let (word, strategy) = (ctx.word(), ctx.strategy);
+        let mut candidates = vec![];
+
+        if self.dirs {
+            candidates.extend(path::complete(word, strategy, Path::is_dir));
+        }
+        if self.files {
+            candidates.extend(path::complete(word, strategy, |_| true));
+        }
+        if self.commands {
+            candidates.extend(
+                Command::autocomplete(word, strategy)
+                    .into_iter()
+                    .filter_map(|name| Candidate::matching(name, word, strategy)),
+            );
+        }
+        if let Some(words) = &self.words {
+            candidates.extend(
+                words
+                    .split_whitespace()
+                    .filter_map(|w| Candidate::matching(w.to_string(), word, strategy)),
+            );
+        }
+        if let Some(name) = &self.function {
+            candidates.extend(call(name, ctx).into_iter().map(Candidate::new));
+        }
+        if let Some(command) = &self.command {
+            candidates.extend(run(command, ctx).into_iter().map(Candidate::new));
+        }
+
+        if let Some(filter) = &self.filter {
+            let (keep, pattern) = match filter.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, filter.as_str()),
+            };
+            let pattern = pattern.replace('&', word);
+            candidates.retain(|c| matches(&pattern, &c.word) == keep);
+        }
+
+        candidates.sort_by(|a, b| a.word.cmp(&b.word));
+        candidates.dedup_by(|a, b| a.word == b.word);
+        rank(&mut candidates);
+        candidates
+    }
+}
+
+/// Prints the spec the way `complete -p` shows it, without the name.
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "complete")?;
+
+        for (on, flag) in [(self.dirs, "-d"), (self.files, "-f"), (self.commands, "-c")] {
+            if on {
+                write!(f, " {flag}")?;
+            }
+        }
+        if let Some(words) = &self.words {
+            write!(f, " -W {}", quote(words))?;
+        }
+        if let Some(function) = &self.function {
+            write!(f, " -F {function}")?;
+        }
+        if let Some(command) = &self.command {
+            write!(f, " -C {}", quote(command))?;
+        }
+        if let Some(filter) = &self.filter {
+            write!(f, " -X {}", quote(filter))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls the `-F` function with the command name, the word and the one
+/// before it as arguments, and `COMP_LINE`, `COMP_POINT`, `COMP_WORDS` and
+/// `COMP_CWORD` set meanwhile. The candidates are what it leaves in
+/// `COMPREPLY`, taken as they are.
+fn call(name: &str, ctx: &Context) -> Vec<String> {
+    const VARS: [&str; 5] = [
+        "COMP_LINE",
+        "COMP_POINT",
+        "COMP_WORDS",
+        "COMP_CWORD",
+        "COMPREPLY",
+    ];
+
+    let status = vars::last_status();
+    vars::set_array("COMP_LINE", vec![ctx.line.to_string()]);
+    vars::set_array("COMP_POINT", vec![ctx.point.to_string()]);
+    vars::set_array("COMP_WORDS", ctx.words.clone());
+    vars::set_array("COMP_CWORD", vec![ctx.cword.to_string()]);
+    vars::unset("COMPREPLY");
+
+    let (command, prev) = words(ctx);
+    let args = [command, ctx.word(), prev].map(String::from);
+    let called = function::call(name, &args);
+    let reply = vars::array("COMPREPLY").unwrap_or_default();
+
+    for var in VARS {
+        vars::unset(var);
+    }
+    vars::set_last_status(status);
+
+    match called {
+        Some(Ok(_)) => reply,
+        _ => vec![],
+    }
+}
+
+/// The command name, and the word before the one being completed.
+fn words<'a>(ctx: &'a Context) -> (&'a str, &'a str) {
+    let name = ctx.words.first().map_or("", String::as_str);
+    let prev = match ctx.cword {
+        0 => "",
+        i => ctx.words[i - 1].as_str(),
+    };
+    (name, prev)
+}
+
+/// Runs `-C` through the shell with the command name, the word and the one
+/// before it as arguments, and `COMP_LINE`, `COMP_POINT`, `COMP_WORDS` and
+/// `COMP_CWORD` in its environment. Each line it prints is a candidate.
+fn run(program: &str, ctx: &Context) -> Vec<String> {
+    let Ok(shell) = std::env::current_exe() else {
+        return vec![];
+    };
+
+    let (name, prev) = words(ctx);
+    let line = format!(
+        "{program} {} {} {}",
+        quote(name),
+        quote(ctx.word()),
+        quote(prev)
+    );
+
+    let output = std::process::Command::new(shell)
+        .arg("-c")
+        .arg(line)
+        .env("COMP_LINE", ctx.line)
+        .env("COMP_POINT", ctx.point.to_string())
+        .env("COMP_WORDS", ctx.words.join(" "))
+        .env("COMP_CWORD", ctx.cword.to_string())
+        .stdin(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Whether `name` matches the glob `pattern`.
+fn matches(pattern: &str, name: &str) -> bool {
+    match (CString::new(pattern), CString::new(name)) {
+        (Ok(pattern), Ok(name)) => unsafe {
+            libc::fnmatch(pattern.as_ptr(), name.as_ptr(), 0) == 0
+        },
+        _ => false,
+    }
+}
+
+/// Single-quotes `s`, so that it is read back as one word.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(words: &[&str]) -> Context<'static> {
+        Context {
+            line: "",
+            point: 0,
+            strategy: Strategy::Prefix,
+            words: words.iter().map(|w| w.to_string()).collect(),
+            cword: words.len() - 1,
+        }
+    }
+
+    #[test]
+    fn it_generates_candidates() {
+        let spec = Spec {
+            words: Some("start stop status restart".into()),
+            ..Spec::default()
+        };
+        let words: Vec<String> = spec
+            .generate(&context(&["svc", "st"]))
+            .into_iter()
+            .map(|c| c.word)
+            .collect();
+        assert_eq!(words, vec!["start", "status", "stop"]);
+
+        let spec = Spec {
+            words: Some("a.rs b.txt c.rs".into()),
+            filter: Some("*.rs".into()),
+            ..Spec::default()
+        };
+        assert_eq!(spec.generate(&context(&["x", ""])).len(), 1);
+
+        let spec = Spec {
+            filter: Some("!*.rs".into()),
+            ..spec
+        };
+        assert_eq!(spec.generate(&context(&["x", ""])).len(), 2);
+    }
+
+    #[test]
+    fn it_prints_specs() {
+        let spec = Spec {
+            dirs: true,
+            words: Some("it's on".into()),
+            function: Some("_svc".into()),
+            command: Some("svc-complete".into()),
+            ..Spec::default()
+        };
+        assert_eq!(
+            spec.to_string(),
+            "complete -d -W 'it'\\''s on' -F _svc -C 'svc-complete'"
+        );
+    }
+
+    #[test]
+    fn it_calls_functions() {
+        let line =
+            "spec-test() { COMPREPLY=(\"$1:$2:$3\" ${COMP_WORDS[COMP_CWORD]}x $COMP_POINT); }";
+        function::define(function::Definition::parse(line).unwrap().unwrap());
+
+        let _state = vars::test_lock();
+        let status = vars::last_status();
+        let spec = Spec {
+            function: Some("spec-test".into()),
+            ..Spec::default()
+        };
+        let ctx = Context {
+            line: "svc -v st",
+            point: 9,
+            ..context(&["svc", "-v", "st"])
+        };
+        let words: Vec<String> = spec.generate(&ctx).into_iter().map(|c| c.word).collect();
+
+        assert_eq!(words, vec!["9", "stx", "svc:st:-v"]);
+        assert_eq!(vars::array("COMPREPLY"), None);
+        assert_eq!(vars::array("COMP_WORDS"), None);
+        assert_eq!(vars::last_status(), status);
+
+        let spec = Spec {
+            function: Some("spec-test-missing".into()),
+            ..Spec::default()
+        };
+        assert!(spec.generate(&ctx).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The line being edited, along with the cursor position in it. The cursor
+/// is a byte offset that always sits between two grapheme clusters, so that
+/// moving and deleting treat `e` + combining accent or a flag as one unit.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Buffer {
+    text: String,
+    cursor: usize,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves the cursor to byte offset `pos`, which must be a grapheme boundary.
+    pub fn set_cursor(&mut self, pos: usize) {
+        self.cursor = pos.min(self.text.len());
+    }
+
+    /// The text left of the cursor.
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// The text right of the cursor.
+    pub fn after_cursor(&self) -> &str {
+        &self.text[self.cursor..]
+    }
+
+    /// Replaces the whole line, leaving the cursor at its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Replaces the `len` bytes left of the cursor with `text`.
+    pub fn replace_before(&mut self, len: usize, text: &str) {
+        self.replace(self.cursor - len..self.cursor, text);
+    }
+
+    /// Replaces the bytes in `range` with `text` and puts the cursor after it.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        self.cursor = range.start + text.len();
+        self.text.replace_range(range, text);
+    }
+
+    pub fn left(&mut self) -> bool {
+        let moved = self.cursor > 0;
+        self.cursor = self.prev_boundary(self.cursor);
+        moved
+    }
+
+    pub fn right(&mut self) -> bool {
+        let moved = self.cursor < self.text.len();
+        self.cursor = self.next_boundary(self.cursor);
+        moved
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Moves to the start of the current or previous word (Alt-B).
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start(is_word_char);
+    }
+
+    /// Moves to the end of the current or next word (Alt-F).
+    pub fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    /// Deletes the grapheme left of the cursor (Backspace).
+    pub fn delete_back(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        let start = self.prev_boundary(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+        true
+    }
+
+    /// Deletes the grapheme under the cursor (Ctrl-D, Del).
+    pub fn delete_forward(&mut self) -> bool {
+        if self.cursor == self.text.len() {
+            return false;
+        }
+        let end = self.next_boundary(self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+        true
+    }
+
+    /// Ctrl-K
+    pub fn kill_to_end(&mut self) -> String {
+        self.text.split_off(self.cursor)
+    }
+
+    /// Ctrl-U
+    pub fn kill_to_start(&mut self) -> String {
+        let killed = self.text.drain(..self.cursor).collect();
+        self.cursor = 0;
+        killed
+    }
+
+    /// Kills back to the previous whitespace (Ctrl-W).
+    pub fn kill_word_back(&mut self) -> String {
+        let start = self.word_start(|c| !c.is_whitespace());
+        let killed = self.text.drain(start..self.cursor).collect();
+        self.cursor = start;
+        killed
+    }
+
+    /// Kills to the end of the current or next word (Alt-D).
+    pub fn kill_word_forward(&mut self) -> String {
+        let end = self.word_end();
+        self.text.drain(self.cursor..end).collect()
+    }
+
+    /// Swaps the graphemes around the cursor and moves past them (Ctrl-T).
+    /// At the end of the line, the last two graphemes are swapped.
+    pub fn transpose(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        if self.cursor == self.text.len() {
+            self.cursor = self.prev_boundary(self.cursor);
+        }
+
+        let start = self.prev_boundary(self.cursor);
+        let end = self.next_boundary(self.cursor);
+        if start == self.cursor || end == self.cursor {
+            self.cursor = self.text.len();
+            return false;
+        }
+
+        let swapped = format!(
+            "{}{}",
+            &self.text[self.cursor..end],
+            &self.text[start..self.cursor]
+        );
+        self.text.replace_range(start..end, &swapped);
+        self.cursor = end;
+        true
+    }
+
+    fn prev_boundary(&self, pos: usize) -> usize {
+        self.text[..pos]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, pos: usize) -> usize {
+        pos + self.text[pos..].graphemes(true).next().map_or(0, str::len)
+    }
+
+    /// Where the word before the cursor starts, skipping what isn't one
+    /// first. Graphemes go by their first char, so that a combining accent is
+    /// part of the word its base letter is in.
+    fn word_start(&self, is_word: fn(char) -> bool) -> usize {
+        let word = |(_, g): &(usize, &str)| g.chars().next().is_some_and(is_word);
+        let mut graphemes = self.text[..self.cursor]
+            .grapheme_indices(true)
+            .rev()
+            .peekable();
+
+        while graphemes.next_if(|g| !word(g)).is_some() {}
+        while graphemes.next_if(word).is_some() {}
+
+        graphemes.peek().map_or(0, |(i, g)| i + g.len())
+    }
+
+    fn word_end(&self) -> usize {
+        let word = |(_, g): &(usize, &str)| g.chars().next().is_some_and(is_word_char);
+        let mut graphemes = self.text[self.cursor..].grapheme_indices(true).peekable();
+
+        while graphemes.next_if(|g| !word(g)).is_some() {}
+        while graphemes.next_if(word).is_some() {}
+
+        self.cursor
+            + graphemes
+                .peek()
+                .map_or(self.text.len() - self.cursor, |(i, _)| *i)
+    }
+}
+
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str, cursor: usize) -> Buffer {
+        let mut buf = Buffer::new();
+        buf.set(text);
+        buf.cursor = cursor;
+        buf
+    }
+
+    #[test]
+    fn it_edits_at_the_cursor() {
+        let mut buf = buffer("ech", 0);
+        buf.insert('>');
+        buf.end();
+        buf.insert('o');
+        assert_eq!(buf.to_string(), ">echo");
+
+        buf.home();
+        assert!(buf.delete_forward());
+        assert!(!buf.delete_back());
+        assert_eq!(buf.to_string(), "echo");
+        assert_eq!(buf.cursor(), 0);
+
+        assert!(!buf.left());
+        buf.end();
+        assert!(!buf.right());
+        assert_eq!(buf.cursor(), 4);
+    }
+
+    #[test]
+    fn it_moves_by_words() {
+        let mut buf = buffer("git commit -m msg", 17);
+        buf.word_left();
+        assert_eq!(buf.cursor(), 14);
+        buf.word_left();
+        assert_eq!(buf.cursor(), 12);
+        buf.word_left();
+        assert_eq!(buf.cursor(), 4);
+
+        buf.word_right();
+        assert_eq!(buf.cursor(), 10);
+        buf.word_right();
+        assert_eq!(buf.cursor(), 13);
+    }
+
+    #[test]
+    fn it_kills_text() {
+        let mut buf = buffer("cat src/main.rs | less", 15);
+        assert_eq!(buf.kill_word_back(), "src/main.rs");
+        assert_eq!(buf.to_string(), "cat  | less");
+
+        let mut buf = buffer("cat src/main.rs", 4);
+        assert_eq!(buf.kill_word_forward(), "src");
+        assert_eq!(buf.kill_to_end(), "/main.rs");
+        assert_eq!(buf.kill_to_start(), "cat ");
+        assert_eq!(buf.to_string(), "");
+    }
+
+    #[test]
+    fn it_transposes_characters() {
+        let mut buf = buffer("sl", 1);
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string(), "ls");
+        assert_eq!(buf.cursor(), 2);
+
+        let mut buf = buffer("gti", 3);
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string(), "git");
+
+        let mut buf = buffer("ab", 0);
+        assert!(!buf.transpose());
+    }
+
+    #[test]
+    fn it_edits_by_grapheme() {
+        let mut buf = Buffer::new();
+        buf.insert_str("caf");
+        buf.insert('e');
+        buf.insert('\u{301}');
+        buf.insert_str(" 日本");
+        assert_eq!(buf.to_string(), "cafe\u{301} 日本");
+
+        assert!(buf.delete_back());
+        assert_eq!(buf.after_cursor(), "");
+        buf.word_left();
+        assert_eq!(buf.before_cursor(), "cafe\u{301} ");
+        buf.left();
+        assert!(buf.delete_back());
+        assert_eq!(buf.to_string(), "caf 日");
+
+        buf.end();
+        buf.left();
+        buf.insert('x');
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string(), "caf 日x");
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string(), "caf x日");
+
+        let mut buf = buffer("cafe\u{301}s x", 0);
+        buf.word_right();
+        assert_eq!(buf.before_cursor(), "cafe\u{301}s");
+        buf.word_left();
+        assert_eq!(buf.cursor(), 0);
+        assert_eq!(buf.kill_word_forward(), "cafe\u{301}s");
+
+        let mut buf = buffer("x cafe\u{301}", 8);
+        buf.word_left();
+        assert_eq!(buf.before_cursor(), "x ");
+        buf.end();
+        assert_eq!(buf.kill_word_back(), "cafe\u{301}");
+    }
+}
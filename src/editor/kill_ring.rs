@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+
+const CAPACITY: usize = 30;
+
+/// Where killed text was, relative to the cursor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// Text removed by the kill commands, newest first, to be put back with
+/// Ctrl-Y and cycled through with Alt-Y.
+#[derive(Debug, Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+    pos: usize,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records killed text. When `merge` is set, as for consecutive kills, it
+    /// joins the newest entry on the side it was killed from instead.
+    pub fn kill(&mut self, text: String, dir: Direction, merge: bool) {
+        if text.is_empty() {
+            return;
+        }
+
+        match self.entries.front_mut() {
+            Some(last) if merge => match dir {
+                Direction::Forward => last.push_str(&text),
+                Direction::Backward => last.insert_str(0, &text),
+            },
+            _ => {
+                self.entries.push_front(text);
+                self.entries.truncate(CAPACITY);
+            }
+        }
+
+        self.pos = 0;
+    }
+
+    /// Returns the newest entry.
+    pub fn yank(&mut self) -> Option<&str> {
+        self.pos = 0;
+        self.entries.front().map(String::as_str)
+    }
+
+    /// Returns the entry before the one yanked last, wrapping around.
+    pub fn rotate(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        self.pos = (self.pos + 1) % self.entries.len();
+        self.entries.get(self.pos).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_killed_text() {
+        let mut ring = KillRing::new();
+        assert_eq!(ring.yank(), None);
+
+        ring.kill("one".into(), Direction::Forward, false);
+        ring.kill("two".into(), Direction::Forward, false);
+        ring.kill(" three".into(), Direction::Forward, true);
+        ring.kill("zero ".into(), Direction::Backward, true);
+        assert_eq!(ring.yank(), Some("zero two three"));
+        assert_eq!(ring.rotate(), Some("one"));
+        assert_eq!(ring.rotate(), Some("zero two three"));
+    }
+}
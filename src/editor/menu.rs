@@ -0,0 +1,153 @@
+use crate::complete::Candidate;
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The candidates Tab and Shift-Tab cycle through when `set -o menucomplete`
+/// is on, with the current one in the line in place of the word.
+#[derive(Debug)]
+pub struct Menu {
+    pub candidates: Vec<Candidate>,
+    pub index: usize,
+    /// Where the current candidate starts and ends in the line.
+    pub start: usize,
+    pub end: usize,
+    /// The quote the word was typed in, which the candidates go in too.
+    pub quote: Option<char>,
+}
+
+impl Menu {
+    /// Starts on the first candidate, or on the last one going backwards, to
+    /// replace the word at `span`.
+    pub fn new(
+        candidates: Vec<Candidate>,
+        span: Range<usize>,
+        quote: Option<char>,
+        forward: bool,
+    ) -> Self {
+        let index = if forward { 0 } else { candidates.len() - 1 };
+        Self {
+            candidates,
+            index,
+            start: span.start,
+            end: span.end,
+            quote,
+        }
+    }
+
+    pub fn current(&self) -> &Candidate {
+        &self.candidates[self.index]
+    }
+
+    pub fn step(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+    }
+
+    /// The rows of the menu with the current candidate highlighted, as many
+    /// as fit in `height` and including the current one.
+    pub fn rows(&self, cols: usize, height: usize) -> Vec<String> {
+        let names: Vec<&str> = self.candidates.iter().map(|c| c.display.as_str()).collect();
+        let rows = columns(&names, cols, Some(self.index));
+        let height = height.max(1);
+        let first = self.index % rows.len() / height * height;
+
+        rows.into_iter().skip(first).take(height).collect()
+    }
+}
+
+/// Lays `names` out down then across in as many columns as fit in `cols`,
+/// as `ls` does. Rows stay short of the last column, so that none of them
+/// wraps, and `selected` is shown in reverse video.
+pub fn columns(names: &[&str], cols: usize, selected: Option<usize>) -> Vec<String> {
+    let names: Vec<String> = names
+        .iter()
+        .map(|name| truncate(name, cols.saturating_sub(1).max(1)))
+        .collect();
+    let width = names.iter().map(|n| n.width()).max().unwrap_or(0) + 2;
+    let ncols = ((cols + 1) / width).max(1);
+    let nrows = names.len().div_ceil(ncols);
+
+    (0..nrows)
+        .map(|row| {
+            let mut line = String::new();
+            let mut pad = 0;
+
+            for i in (row..names.len()).step_by(nrows) {
+                line.push_str(&" ".repeat(pad));
+                if selected == Some(i) {
+                    line.push_str(&format!("\x1b[7m{}\x1b[0m", names[i]));
+                } else {
+                    line.push_str(&names[i]);
+                }
+                pad = width - names[i].width();
+            }
+
+            line
+        })
+        .collect()
+}
+
+/// Cuts `name` down to at most `width` columns.
+fn truncate(name: &str, width: usize) -> String {
+    let mut cut = String::new();
+
+    for g in name.graphemes(true) {
+        if cut.width() + g.width() > width {
+            break;
+        }
+        cut.push_str(g);
+    }
+
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lays_out_columns() {
+        let names = ["a", "bb", "c", "dddd", "e"];
+        assert_eq!(
+            columns(&names, 13, None),
+            vec!["a     dddd", "bb    e", "c"]
+        );
+        assert_eq!(columns(&names, 80, None), vec!["a     bb    c     dddd  e"]);
+        assert_eq!(columns(&names, 4, None), ["a", "bb", "c", "ddd", "e"]);
+        assert_eq!(columns(&["日本語"], 4, None), vec!["日"]);
+        assert_eq!(
+            columns(&["a", "b"], 80, Some(1)),
+            vec!["a  \x1b[7mb\x1b[0m"]
+        );
+    }
+
+    #[test]
+    fn it_cycles_through_candidates() {
+        let candidates: Vec<Candidate> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|w| Candidate {
+                word: w.to_string(),
+                display: w.to_string(),
+                space: true,
+                score: 0,
+            })
+            .collect();
+
+        let mut menu = Menu::new(candidates, 0..0, None, false);
+        assert_eq!(menu.current().word, "e");
+        menu.step(true);
+        assert_eq!(menu.current().word, "a");
+        menu.step(false);
+        menu.step(false);
+        assert_eq!(menu.current().word, "d");
+
+        assert_eq!(menu.rows(3, 2), vec!["c", "\x1b[7md\x1b[0m"]);
+        assert_eq!(menu.rows(80, 2).len(), 1);
+    }
+}
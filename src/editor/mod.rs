@@ -0,0 +1,593 @@
+mod buffer;
+mod kill_ring;
+mod menu;
+mod paste;
+mod search;
+mod vi;
+
+use buffer::Buffer;
+use kill_ring::{Direction, KillRing};
+use menu::Menu;
+use paste::{Paste, PASTE};
+use vi::{Step, Vi};
+
+use crate::complete::{self, Candidate};
+use crate::{cmd, common_parts, history, jobs, Result};
+
+use console::{Key, Term};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// How many candidates can be listed without asking first.
+const QUERY_ITEMS: usize = 100;
+
+static VI: AtomicBool = AtomicBool::new(false);
+static MENU_COMPLETE: AtomicBool = AtomicBool::new(false);
+
+/// Switches between vi and Emacs editing (`set -o vi`, `set -o emacs`).
+pub fn set_vi(on: bool) {
+    VI.store(on, Ordering::Relaxed);
+}
+
+pub fn is_vi() -> bool {
+    VI.load(Ordering::Relaxed)
+}
+
+/// Makes Tab and Shift-Tab cycle through the candidates rather than list
+/// them (`set -o menucomplete`).
+pub fn set_menu_complete(on: bool) {
+    MENU_COMPLETE.store(on, Ordering::Relaxed);
+}
+
+pub fn is_menu_complete() -> bool {
+    MENU_COMPLETE.load(Ordering::Relaxed)
+}
+
+/// How reading a line ended.
+#[derive(Debug, PartialEq)]
+pub enum Line {
+    Input(String),
+    Interrupted,
+    /// Ctrl-D on an empty line, or the terminal went away.
+    Eof,
+}
+
+/// What the previous key did, which decides whether a kill joins the one
+/// before it and whether Alt-Y may replace the text just yanked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Last {
+    Kill,
+    /// The length in bytes of what was yanked.
+    Yank(usize),
+    Other,
+}
+
+/// Reads command lines from the terminal with Emacs-style editing keys, or
+/// vi-style ones once `set -o vi` was run.
+pub struct Editor {
+    term: Term,
+    kill_ring: KillRing,
+    /// The row the cursor is on, counted from the one the prompt starts on.
+    cursor_row: usize,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            term: Term::stdout(),
+            kill_ring: KillRing::new(),
+            cursor_row: 0,
+        }
+    }
+
+    /// Shows `prompt` and edits a line until Enter, Ctrl-C or Ctrl-D is
+    /// pressed. In vi mode, the prompt starts with the current mode.
+    ///
+    /// Bracketed paste is on meanwhile, so that pasted text goes into the
+    /// line as it is rather than being run or completed as it arrives.
+    pub fn read_line(&mut self, prompt: &str) -> Result<Line> {
+        write!(self.term, "\x1b[?2004h")?;
+        let line = self.edit(prompt);
+        write!(self.term, "\x1b[?2004l")?;
+        line
+    }
+
+    fn edit(&mut self, base: &str) -> Result<Line> {
+        let mut buf = Buffer::new();
+        let mut candidates: Vec<Candidate> = vec![];
+        let mut menu: Option<Menu> = None;
+        let mut nav = history::Navigator::new();
+        let mut last = Last::Other;
+        let mut vi = is_vi().then(Vi::new);
+        let mut prompt = mode_prompt(base, vi.as_ref());
+
+        self.cursor_row = 0;
+        write!(self.term, "{prompt}")?;
+
+        loop {
+            let Ok(key) = self.term.read_key_raw() else {
+                self.finish(&prompt, &mut buf)?;
+                writeln!(self.term)?;
+                return Ok(Line::Eof);
+            };
+            let mut action = Last::Other;
+            let global = matches!(key, Key::Enter | Key::CtrlC | Key::Char('\x04' | '\x0c'))
+                || matches!(&key, Key::UnknownEscSeq(seq) if seq[..] == PASTE);
+
+            if let Some(vi) = vi.as_mut().filter(|_| !global) {
+                let step = vi.handle(&key, &mut buf);
+
+                match step {
+                    Step::Pass | Step::Done => {}
+                    Step::Bell => write!(self.term, "\x07")?,
+                    Step::HistoryPrev => {
+                        if let Some(line) = nav.prev(buf.as_str()) {
+                            buf.set(&line);
+                            buf.home();
+                        }
+                    }
+                    Step::HistoryNext => {
+                        if let Some(line) = nav.next() {
+                            buf.set(&line);
+                            buf.home();
+                        }
+                    }
+                    Step::Visual => {
+                        let line = self.visual(&prompt, &mut buf)?;
+                        return Ok(Line::Input(line));
+                    }
+                }
+
+                if step != Step::Pass {
+                    candidates.clear();
+                    menu = None;
+                    prompt = mode_prompt(base, Some(vi));
+                    self.refresh(&prompt, &buf)?;
+                    continue;
+                }
+            }
+
+            match key {
+                Key::CtrlC => {
+                    self.finish(&prompt, &mut buf)?;
+                    writeln!(self.term, "^C")?;
+                    return Ok(Line::Interrupted);
+                }
+                Key::Enter => break,
+                Key::Char('\x04') if buf.as_str().is_empty() => {
+                    writeln!(self.term)?;
+                    return Ok(Line::Eof);
+                }
+                Key::Char('\x0c') => {
+                    self.term.clear_screen()?;
+                    self.cursor_row = 0;
+                }
+                Key::Tab => {
+                    self.complete(&prompt, &mut buf, &mut candidates, &mut menu, true)?;
+                    continue;
+                }
+                Key::BackTab if menu.is_some() || is_menu_complete() => {
+                    self.complete(&prompt, &mut buf, &mut candidates, &mut menu, false)?;
+                    continue;
+                }
+                Key::Char('\x12') => {
+                    self.clear()?;
+
+                    match search::reverse(&mut self.term)? {
+                        search::Outcome::Run(line) => {
+                            buf.set(&line);
+                            break;
+                        }
+                        search::Outcome::Edit(line) => buf.set(&line),
+                        search::Outcome::Cancel => {}
+                    }
+                }
+                Key::ArrowLeft | Key::Char('\x02') => {
+                    buf.left();
+                }
+                Key::ArrowRight | Key::Char('\x06') => {
+                    buf.right();
+                }
+                Key::Home => buf.home(),
+                Key::End => buf.end(),
+                Key::ArrowUp | Key::Char('\x10') => {
+                    if let Some(line) = nav.prev(&buf.to_string()) {
+                        buf.set(&line);
+                    }
+                }
+                Key::ArrowDown | Key::Char('\x0e') => {
+                    if let Some(line) = nav.next() {
+                        buf.set(&line);
+                    }
+                }
+                Key::Backspace => {
+                    buf.delete_back();
+                }
+                Key::Del | Key::Char('\x04') => {
+                    buf.delete_forward();
+                }
+                Key::Char('\x0b') => {
+                    let text = buf.kill_to_end();
+                    action = self.kill(text, Direction::Forward, last);
+                }
+                Key::Char('\x15') => {
+                    let text = buf.kill_to_start();
+                    action = self.kill(text, Direction::Backward, last);
+                }
+                Key::Char('\x17') => {
+                    let text = buf.kill_word_back();
+                    action = self.kill(text, Direction::Backward, last);
+                }
+                Key::Char('\x19') => {
+                    if let Some(text) = self.kill_ring.yank() {
+                        buf.insert_str(text);
+                        action = Last::Yank(text.len());
+                    }
+                }
+                Key::Char('\x14') => {
+                    buf.transpose();
+                }
+                Key::UnknownEscSeq(seq) if seq[..] == PASTE && self.paste_marker() == Some('0') => {
+                    let text = self.read_paste();
+                    buf.insert_str(&text);
+                }
+                Key::UnknownEscSeq(seq) => match seq.as_slice() {
+                    ['b'] => buf.word_left(),
+                    ['f'] => buf.word_right(),
+                    ['d'] => {
+                        let text = buf.kill_word_forward();
+                        action = self.kill(text, Direction::Forward, last);
+                    }
+                    ['y'] => {
+                        if let Last::Yank(len) = last {
+                            if let Some(text) = self.kill_ring.rotate() {
+                                buf.replace_before(len, text);
+                                action = Last::Yank(text.len());
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Key::Char(c) if c.is_control() => {}
+                Key::Char(c) => buf.insert(c),
+                _ => {}
+            }
+
+            last = action;
+            candidates = vec![];
+            menu = None;
+            self.refresh(&prompt, &buf)?;
+        }
+
+        self.finish(&prompt, &mut buf)?;
+        writeln!(self.term)?;
+        Ok(Line::Input(buf.to_string()))
+    }
+
+    /// Reads the `x~` that the terminal library leaves over from an
+    /// `ESC [ 20x ~` paste marker, and returns `x`.
+    fn paste_marker(&mut self) -> Option<char> {
+        let digit = self.term.read_key_raw().ok()?;
+        let tilde = self.term.read_key_raw().ok()?;
+        paste::marker(digit, tilde)
+    }
+
+    /// Reads pasted text up to the end of the paste.
+    fn read_paste(&mut self) -> String {
+        let mut paste = Paste::default();
+
+        while let Ok(key) = self.term.read_key_raw() {
+            if paste.feed(key) {
+                break;
+            }
+        }
+
+        paste.text
+    }
+
+    fn kill(&mut self, text: String, dir: Direction, last: Last) -> Last {
+        self.kill_ring.kill(text, dir, last == Last::Kill);
+        Last::Kill
+    }
+
+    /// Completes the word before the cursor on the first Tab and lists the
+    /// candidates on the second one. With `set -o menucomplete`, Tab and
+    /// Shift-Tab put each candidate in the line in turn instead.
+    fn complete(
+        &mut self,
+        prompt: &str,
+        buf: &mut Buffer,
+        candidates: &mut Vec<Candidate>,
+        menu: &mut Option<Menu>,
+        forward: bool,
+    ) -> Result<()> {
+        if let Some(menu) = menu.as_mut() {
+            menu.step(forward);
+            choose(buf, menu);
+            return self.draw(prompt, buf, Some(menu));
+        }
+
+        if !candidates.is_empty() {
+            let names: Vec<&str> = candidates.iter().map(|c| c.display.as_str()).collect();
+            let cursor = buf.cursor();
+
+            self.finish(prompt, buf)?;
+            writeln!(self.term)?;
+            self.list(&names)?;
+            self.cursor_row = 0;
+            buf.set_cursor(cursor);
+            return self.refresh(prompt, buf);
+        }
+
+        let completion = complete::complete(buf.as_str(), buf.cursor());
+        let (range, quote) = (completion.start..completion.end, completion.quote);
+        *candidates = completion.candidates;
+
+        if let [only] = candidates.as_slice() {
+            buf.replace(range, &complete::escape(&only.word, quote, only.space));
+            if only.space && !buf.after_cursor().starts_with(char::is_whitespace) {
+                buf.insert(' ');
+            } else if only.space {
+                buf.right();
+            }
+            candidates.clear();
+            return self.refresh(prompt, buf);
+        }
+
+        if is_menu_complete() && !candidates.is_empty() {
+            let candidates = std::mem::take(candidates);
+            let mut cycle = Menu::new(candidates, range, quote, forward);
+            choose(buf, &mut cycle);
+            self.draw(prompt, buf, Some(&cycle))?;
+            *menu = Some(cycle);
+            return Ok(());
+        }
+
+        let words: Vec<String> = candidates.iter().map(|c| c.word.clone()).collect();
+        let common = common_parts(&words);
+
+        // Only the part before the cursor is a prefix of the candidates. A
+        // word matched some other way is replaced by what they have in
+        // common as long as that says at least as much.
+        if common != completion.word && common.len() >= completion.word.len() {
+            let range = range.start..buf.cursor();
+            buf.replace(range, &complete::escape(&common, quote, false));
+            self.refresh(prompt, buf)
+        } else {
+            write!(self.term, "\x07")?;
+            Ok(())
+        }
+    }
+
+    /// Lists `names` in columns, asking first when there are more than
+    /// `QUERY_ITEMS` and pausing at `--More--` after each screenful: Space
+    /// shows the next one, Enter the next row, and `q` stops.
+    fn list(&mut self, names: &[&str]) -> Result<()> {
+        if names.len() > QUERY_ITEMS {
+            write!(
+                self.term,
+                "Display all {} possibilities? (y or n)",
+                names.len()
+            )?;
+            let yes = self.ask(&['y', 'Y', ' '], &['n', 'N', 'q'])?;
+            writeln!(self.term)?;
+            if !yes {
+                return Ok(());
+            }
+        }
+
+        let (height, cols) = self.term.size();
+        let page = (height as usize).saturating_sub(1).max(1);
+        let mut more = page;
+
+        for (i, row) in menu::columns(names, cols as usize, None).iter().enumerate() {
+            if i == more {
+                write!(self.term, "--More--")?;
+                let key = self.term.read_key_raw();
+                write!(self.term, "\r\x1b[K")?;
+
+                match key {
+                    Ok(Key::Char(' ' | 'y' | 'Y')) => more += page,
+                    Ok(Key::Enter | Key::Char('j')) => more += 1,
+                    _ => break,
+                }
+            }
+            writeln!(self.term, "{row}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for one of the `yes` or `no` keys, ringing the bell at others.
+    /// Ctrl-C, Esc or the terminal going away count as no.
+    fn ask(&mut self, yes: &[char], no: &[char]) -> Result<bool> {
+        loop {
+            match self.term.read_key_raw() {
+                Ok(Key::Char(c)) if yes.contains(&c) => return Ok(true),
+                Ok(Key::Char(c)) if no.contains(&c) => return Ok(false),
+                Ok(Key::CtrlC | Key::Escape) | Err(_) => return Ok(false),
+                Ok(_) => write!(self.term, "\x07")?,
+            }
+        }
+    }
+
+    /// Redraws the prompt and the line, which may span several rows, and puts
+    /// the cursor back where it is in the line.
+    fn refresh(&mut self, prompt: &str, buf: &Buffer) -> Result<()> {
+        self.draw(prompt, buf, None)
+    }
+
+    /// Redraws like `refresh`, with `menu` below the line if there is one.
+    fn draw(&mut self, prompt: &str, buf: &Buffer, menu: Option<&Menu>) -> Result<()> {
+        let (height, cols) = self.term.size();
+        let cols = (cols as usize).max(1);
+        let start = advance((0, 0), prompt, cols);
+        let (row, col) = advance(start, buf.before_cursor(), cols);
+        let end = advance((row, col), buf.after_cursor(), cols);
+
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        out.push_str(&format!("\r\x1b[J{prompt}{buf}"));
+
+        // Filling the last column leaves the cursor on it rather than on the
+        // next row, so wrap by hand to match the position `advance` gives.
+        if end.0 > 0 && end.1 == 0 {
+            out.push_str("\r\n");
+        }
+
+        let mut below = 0;
+        if let Some(menu) = menu {
+            let rows = menu.rows(cols, (height as usize).saturating_sub(end.0 + 1));
+            for line in &rows {
+                out.push_str(&format!("\r\n{line}"));
+            }
+            below = rows.len();
+        }
+
+        if end.0 + below > row {
+            out.push_str(&format!("\x1b[{}A", end.0 + below - row));
+        }
+        out.push('\r');
+        if col > 0 {
+            out.push_str(&format!("\x1b[{col}C"));
+        }
+
+        self.cursor_row = row;
+        write!(self.term, "{out}")?;
+        Ok(())
+    }
+
+    /// Opens the line in `$VISUAL` or `$EDITOR` (`vi` by default) and returns
+    /// what was saved, to be run right away (`v` in vi mode).
+    fn visual(&mut self, prompt: &str, buf: &mut Buffer) -> Result<String> {
+        self.finish(prompt, buf)?;
+        writeln!(self.term)?;
+
+        let file = tempfile::Builder::new()
+            .prefix("shell-edit-")
+            .suffix(".sh")
+            .tempfile()?;
+        std::fs::write(file.path(), format!("{buf}\n"))?;
+
+        let editor = ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))
+            .unwrap_or_else(|| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let Some(path) = cmd::program(program) else {
+            eprintln!("{program}: command not found");
+            return Ok(String::new());
+        };
+
+        // Run as a job of its own like any other program, so that it gets the
+        // terminal and the shell notices when it's stopped.
+        let mut command = std::process::Command::new(path);
+        command.args(words).arg(file.path());
+        let job = format!("{editor} {}", file.path().display());
+
+        match jobs::run_foreground(&mut command, &job) {
+            Ok(jobs::Status::Exited(0)) => {
+                let line = std::fs::read_to_string(file.path())?;
+                let line = line.trim_end().to_string();
+                writeln!(self.term, "{line}")?;
+                Ok(line)
+            }
+            Ok(jobs::Status::Stopped(job, _)) => {
+                // The editor may be continued later, and needs its file then.
+                let _ = file.keep();
+                eprintln!("\n{job}");
+                Ok(String::new())
+            }
+            Ok(_) => Ok(String::new()),
+            Err(err) => {
+                eprintln!("{program}: {err}");
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// Moves the cursor past the end of the line, so that what follows is
+    /// written below all of it.
+    fn finish(&mut self, prompt: &str, buf: &mut Buffer) -> Result<()> {
+        buf.end();
+        self.refresh(prompt, buf)
+    }
+
+    /// Erases the prompt and the line, leaving the cursor where they started.
+    fn clear(&mut self) -> Result<()> {
+        if self.cursor_row > 0 {
+            write!(self.term, "\x1b[{}A", self.cursor_row)?;
+        }
+        write!(self.term, "\r\x1b[J")?;
+        self.cursor_row = 0;
+        Ok(())
+    }
+}
+
+/// Puts the current candidate of `menu` in the line in place of the one
+/// before it.
+fn choose(buf: &mut Buffer, menu: &mut Menu) {
+    let current = menu.current();
+    let word = complete::escape(&current.word, menu.quote, current.space);
+    buf.replace(menu.start..menu.end, &word);
+    menu.end = menu.start + word.len();
+}
+
+/// Prefixes `prompt` with the vi mode indicator when in vi mode.
+fn mode_prompt(prompt: &str, vi: Option<&Vi>) -> String {
+    match vi {
+        Some(vi) => format!("{}{prompt}", vi.indicator()),
+        None => prompt.to_string(),
+    }
+}
+
+/// Returns where the cursor ends up after writing `text` from `(row, col)`
+/// on rows `cols` wide. A character too wide for the rest of a row goes on
+/// the next one, as terminals do, and Tabs go to the next multiple of 8.
+fn advance((mut row, mut col): (usize, usize), text: &str, cols: usize) -> (usize, usize) {
+    for g in text.graphemes(true) {
+        let width = match g {
+            "\n" => {
+                row += 1;
+                col = 0;
+                continue;
+            }
+            "\t" => ((col / 8 + 1) * 8).min(cols - 1).saturating_sub(col),
+            g => g.width(),
+        };
+
+        if col + width > cols {
+            row += 1;
+            col = 0;
+        }
+        col += width;
+
+        if col == cols {
+            row += 1;
+            col = 0;
+        }
+    }
+
+    (row, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lays_out_wrapped_lines() {
+        assert_eq!(advance((0, 0), "$ echo", 80), (0, 6));
+        assert_eq!(advance((0, 0), "$ echo", 6), (1, 0));
+        assert_eq!(advance((0, 2), "e\u{301}t\u{e9}", 80), (0, 5));
+        assert_eq!(advance((0, 2), "日本語", 80), (0, 8));
+        assert_eq!(advance((0, 3), "日本", 6), (1, 2));
+        assert_eq!(advance((0, 2), "a\tb\ncd", 80), (1, 2));
+        assert_eq!(advance((0, 2), "\t", 6), (0, 5));
+    }
+}
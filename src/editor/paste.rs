@@ -0,0 +1,153 @@
+use console::Key;
+
+/// How the terminal library reports the start of `ESC [ 200 ~` and
+/// `ESC [ 201 ~`, which surround pasted text.
+pub const PASTE: [char; 3] = ['[', '2', '0'];
+
+/// What paste markers start with, before their digit and `~`.
+const MARKER: &str = "\x1b[20";
+
+/// The `x` of an `ESC [ 20x ~` paste marker, from the two keys the terminal
+/// library leaves over after its start.
+pub fn marker(digit: Key, tilde: Key) -> Option<char> {
+    match (digit, tilde) {
+        (Key::Char(c), Key::Char('~')) => Some(c),
+        _ => None,
+    }
+}
+
+/// Pasted text, taken key by key up to the `ESC [ 201 ~` ending it. Tabs
+/// and newlines are kept as they are rather than completing or entering
+/// the line.
+#[derive(Debug, Default)]
+pub struct Paste {
+    pub text: String,
+    /// The part of a marker read so far. The terminal library splits a
+    /// marker into keys wherever a read ended, so it may come in pieces.
+    pending: String,
+}
+
+impl Paste {
+    /// Takes the next key, and tells whether it ended the paste. Markers
+    /// other than the end one are dropped.
+    pub fn feed(&mut self, key: Key) -> bool {
+        let chars: String = match key {
+            Key::Escape => "\x1b".into(),
+            Key::UnknownEscSeq(seq) => std::iter::once('\x1b').chain(seq).collect(),
+            Key::Enter => "\n".into(),
+            Key::Tab => "\t".into(),
+            Key::Char(c) => c.into(),
+            _ => {
+                self.flush();
+                return false;
+            }
+        };
+
+        chars.chars().any(|c| self.push(c))
+    }
+
+    fn push(&mut self, c: char) -> bool {
+        self.pending.push(c);
+
+        let in_marker = match self.pending.len() {
+            n if n <= MARKER.len() => MARKER.starts_with(&self.pending),
+            n if n == MARKER.len() + 1 => c.is_ascii_digit(),
+            _ => c == '~',
+        };
+
+        if !in_marker {
+            self.pending.pop();
+            self.flush();
+            if c == '\x1b' {
+                self.pending.push(c);
+            } else {
+                self.insert(c);
+            }
+            return false;
+        }
+
+        if self.pending.len() < MARKER.len() + 2 {
+            return false;
+        }
+
+        let digit = self.pending.chars().nth(MARKER.len());
+        self.pending.clear();
+        digit == Some('1')
+    }
+
+    /// Takes what looked like the start of a marker as text after all.
+    fn flush(&mut self) {
+        for c in std::mem::take(&mut self.pending).chars() {
+            self.insert(c);
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        if !c.is_control() || c == '\n' || c == '\t' {
+            self.text.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(keys: Vec<Key>) -> (String, bool) {
+        let mut paste = Paste::default();
+        let ended = keys.into_iter().any(|key| paste.feed(key));
+        (paste.text, ended)
+    }
+
+    fn chars(s: &str) -> Vec<Key> {
+        s.chars().map(Key::Char).collect()
+    }
+
+    #[test]
+    fn it_reads_markers() {
+        assert_eq!(marker(Key::Char('0'), Key::Char('~')), Some('0'));
+        assert_eq!(marker(Key::Char('1'), Key::Char('~')), Some('1'));
+        assert_eq!(marker(Key::Char('1'), Key::Char('x')), None);
+        assert_eq!(marker(Key::Tab, Key::Char('~')), None);
+    }
+
+    #[test]
+    fn it_keeps_tabs_and_newlines() {
+        let mut keys = chars("ls");
+        keys.extend([Key::Tab, Key::Enter, Key::Char('x')]);
+        keys.push(Key::UnknownEscSeq(PASTE.to_vec()));
+        keys.extend(chars("1~y"));
+        assert_eq!(feed_all(keys), ("ls\t\nx".into(), true));
+    }
+
+    #[test]
+    fn it_ends_on_the_end_marker_only() {
+        let mut keys = chars("a");
+        keys.push(Key::UnknownEscSeq(PASTE.to_vec()));
+        keys.extend(chars("0~b"));
+        keys.push(Key::UnknownEscSeq(vec!['[', '2', '1']));
+        keys.extend(chars("~"));
+        assert_eq!(feed_all(keys), ("ab[21~".into(), false));
+
+        let keys = vec![Key::Char('a'), Key::UnknownEscSeq(PASTE.to_vec())];
+        assert_eq!(feed_all(keys), ("a".into(), false));
+    }
+
+    #[test]
+    fn it_ends_on_a_marker_split_across_reads() {
+        let mut keys = chars("a");
+        keys.push(Key::Escape);
+        keys.extend(chars("[201~"));
+        assert_eq!(feed_all(keys), ("a".into(), true));
+
+        let mut keys = chars("a");
+        keys.push(Key::UnknownEscSeq(vec!['[', '2']));
+        keys.extend(chars("01~"));
+        assert_eq!(feed_all(keys), ("a".into(), true));
+
+        let mut keys = chars("a");
+        keys.push(Key::Escape);
+        keys.extend(chars("[2x"));
+        assert_eq!(feed_all(keys), ("a[2x".into(), false));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{history, Result};
+
+use console::{Key, Term};
+use std::io::Write;
+
+/// What the user chose to do with the line found by a search.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Run(String),
+    Edit(String),
+    Cancel,
+}
+
+/// Runs an incremental reverse search over the history (Ctrl-R), redrawing
+/// the prompt line as `(reverse-i-search)`query': match`.
+///
+/// Typing refines the query, Ctrl-R again looks for an older match, Enter
+/// runs the match, Esc or a cursor key keeps it for editing, and Ctrl-C or
+/// Ctrl-G goes back to the line as it was.
+pub fn reverse(term: &mut Term) -> Result<Outcome> {
+    let mut query = String::new();
+    let mut found: Option<(usize, String)> = None;
+    let mut failed = false;
+
+    loop {
+        let line = found.as_ref().map(|(_, l)| l.as_str()).unwrap_or_default();
+        let label = if failed {
+            "failed reverse-i-search"
+        } else {
+            "reverse-i-search"
+        };
+        term.clear_line()?;
+        write!(term, "({label})`{query}': {line}")?;
+
+        let before = match term.read_key_raw()? {
+            Key::Char('\x12') => found.as_ref().map_or(history::len(), |(i, _)| *i),
+            Key::Char('\x07') | Key::CtrlC => return Ok(Outcome::Cancel),
+            Key::Char(c) if !c.is_control() => {
+                query.push(c);
+                found.as_ref().map_or(history::len(), |(i, _)| i + 1)
+            }
+            Key::Backspace => {
+                query.pop();
+                history::len()
+            }
+            Key::Enter => return Ok(Outcome::Run(line.to_string())),
+            Key::Escape
+            | Key::ArrowLeft
+            | Key::ArrowRight
+            | Key::ArrowUp
+            | Key::ArrowDown
+            | Key::Home
+            | Key::End => return Ok(Outcome::Edit(line.to_string())),
+            _ => continue,
+        };
+
+        if query.is_empty() {
+            found = None;
+            failed = false;
+            continue;
+        }
+
+        match history::search(&query, before) {
+            Some(m) => {
+                found = Some(m);
+                failed = false;
+            }
+            None => failed = true,
+        }
+    }
+}
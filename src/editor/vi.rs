@@ -0,0 +1,682 @@
+use super::buffer::Buffer;
+
+use console::Key;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What the editor should do after a key went through vi mode.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// The key is an insert mode one, to be handled like in Emacs mode.
+    Pass,
+    Done,
+    Bell,
+    HistoryPrev,
+    HistoryNext,
+    /// Opens the line in `$EDITOR` (`v`).
+    Visual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    WordForward(bool),
+    WordBack(bool),
+    WordEnd(bool),
+    Start,
+    FirstNonBlank,
+    End,
+    /// `f`, `t`, `F` and `T`: the character, whether to stop before it and
+    /// whether to look backwards.
+    Find(char, bool, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Move(Motion),
+    Apply(Op, Motion),
+    /// `dd`, `cc` and `yy`
+    Line(Op),
+    DeleteChar,
+    DeleteBack,
+    Replace(char),
+    Paste(bool),
+    Undo,
+    Repeat,
+    Insert(Insert),
+    HistoryPrev,
+    HistoryNext,
+    Visual,
+}
+
+/// Where `i`, `a`, `I` and `A` start inserting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Insert {
+    Before,
+    After,
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cmd {
+    count: Option<usize>,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq)]
+enum Parse<T> {
+    Incomplete,
+    Invalid,
+    Done(T),
+}
+
+/// The state of vi mode while a line is edited.
+#[derive(Debug, Default)]
+pub struct Vi {
+    pub insert: bool,
+    keys: Vec<char>,
+    register: String,
+    undo: Vec<(String, usize)>,
+    /// The change command that started the current insert, and what was
+    /// typed since.
+    inserting: Option<(Cmd, String)>,
+    last_change: Option<(Cmd, String)>,
+}
+
+impl Vi {
+    /// Vi mode starts every line in insert mode.
+    pub fn new() -> Self {
+        Self {
+            insert: true,
+            ..Self::default()
+        }
+    }
+
+    /// The mode indicator shown before the prompt.
+    pub fn indicator(&self) -> &'static str {
+        if self.insert {
+            "(ins)"
+        } else {
+            "(cmd)"
+        }
+    }
+
+    pub fn handle(&mut self, key: &Key, buf: &mut Buffer) -> Step {
+        if self.insert {
+            return self.insert_key(key, buf);
+        }
+
+        let c = match key {
+            Key::Char(c) if !c.is_control() => *c,
+            Key::ArrowLeft | Key::Backspace => 'h',
+            Key::ArrowRight => 'l',
+            Key::ArrowUp => 'k',
+            Key::ArrowDown => 'j',
+            Key::Home => '0',
+            Key::End => '$',
+            Key::Del => 'x',
+            _ => {
+                self.keys.clear();
+                return Step::Bell;
+            }
+        };
+        self.keys.push(c);
+
+        match parse(&self.keys) {
+            Parse::Incomplete => Step::Done,
+            Parse::Invalid => {
+                self.keys.clear();
+                Step::Bell
+            }
+            Parse::Done(cmd) => {
+                self.keys.clear();
+                self.run(cmd, buf)
+            }
+        }
+    }
+
+    fn insert_key(&mut self, key: &Key, buf: &mut Buffer) -> Step {
+        match key {
+            Key::Escape => {
+                self.insert = false;
+                if let Some(change) = self.inserting.take() {
+                    self.last_change = Some(change);
+                }
+                buf.left();
+                return Step::Done;
+            }
+            // Esc and the next key typed quickly arrive as one Alt key.
+            Key::UnknownEscSeq(seq) if seq.len() == 1 => {
+                self.insert_key(&Key::Escape, buf);
+                return self.handle(&Key::Char(seq[0]), buf);
+            }
+            Key::Char(c) if !c.is_control() => {
+                if let Some((_, text)) = self.inserting.as_mut() {
+                    text.push(*c);
+                }
+            }
+            Key::Backspace => {
+                if let Some((_, text)) = self.inserting.as_mut() {
+                    text.pop();
+                }
+            }
+            _ => {}
+        }
+
+        Step::Pass
+    }
+
+    fn run(&mut self, cmd: Cmd, buf: &mut Buffer) -> Step {
+        let count = cmd.count.unwrap_or(1);
+        let line = buf.as_str().to_string();
+        let text = Text::new(&line);
+        let i = text.index(buf.cursor());
+
+        let step = match cmd.kind {
+            Kind::Move(m) => match text.target(m, i, count) {
+                Some((j, _)) => {
+                    buf.set_cursor(text.offset(j.min(text.last())));
+                    Step::Done
+                }
+                None => Step::Bell,
+            },
+            Kind::Apply(op, m) => {
+                // `cw` changes to the end of the word, like `ce`.
+                let m = match m {
+                    Motion::WordForward(big) if op == Op::Change && !text.is_blank(i) => {
+                        Motion::WordEnd(big)
+                    }
+                    m => m,
+                };
+
+                match text.target(m, i, count) {
+                    Some((j, inclusive)) => {
+                        let (start, end) = if j >= i {
+                            (i, if inclusive { j + 1 } else { j })
+                        } else {
+                            (j, i)
+                        };
+                        let range = text.offset(start)..text.offset(end.min(text.len()));
+                        self.apply(op, cmd, range, buf)
+                    }
+                    None => Step::Bell,
+                }
+            }
+            Kind::Line(op) => self.apply(op, cmd, 0..line.len(), buf),
+            Kind::DeleteChar if i < text.len() => {
+                let range = text.offset(i)..text.offset((i + count).min(text.len()));
+                self.apply(Op::Delete, cmd, range, buf)
+            }
+            Kind::DeleteBack if i > 0 => {
+                let range = text.offset(i.saturating_sub(count))..text.offset(i);
+                self.apply(Op::Delete, cmd, range, buf)
+            }
+            Kind::Replace(c) if i + count <= text.len() => {
+                self.save(buf);
+                let range = text.offset(i)..text.offset(i + count);
+                buf.replace(range, &c.to_string().repeat(count));
+                buf.left();
+                self.last_change = Some((cmd, String::new()));
+                Step::Done
+            }
+            Kind::Paste(before) if !self.register.is_empty() => {
+                self.save(buf);
+                let at = if before || text.len() == 0 { i } else { i + 1 };
+                buf.set_cursor(text.offset(at));
+                buf.insert_str(&self.register.repeat(count));
+                buf.left();
+                self.last_change = Some((cmd, String::new()));
+                Step::Done
+            }
+            Kind::Undo => match self.undo.pop() {
+                Some((line, cursor)) => {
+                    buf.set(&line);
+                    buf.set_cursor(cursor);
+                    Step::Done
+                }
+                None => Step::Bell,
+            },
+            Kind::Repeat => match self.last_change.clone() {
+                Some((last, inserted)) => {
+                    let count = cmd.count.or(last.count);
+                    let step = self.run(Cmd { count, ..last }, buf);
+
+                    if self.insert {
+                        buf.insert_str(&inserted);
+                        self.inserting = Some((last, inserted));
+                        self.insert_key(&Key::Escape, buf);
+                    }
+                    step
+                }
+                None => Step::Bell,
+            },
+            Kind::Insert(at) => {
+                let at = match at {
+                    Insert::Before => i,
+                    Insert::After => (i + 1).min(text.len()),
+                    Insert::Start => (0..text.len()).find(|&j| !text.is_blank(j)).unwrap_or(0),
+                    Insert::End => text.len(),
+                };
+                buf.set_cursor(text.offset(at));
+                self.save(buf);
+                self.start_insert(cmd);
+                Step::Done
+            }
+            Kind::HistoryPrev => Step::HistoryPrev,
+            Kind::HistoryNext => Step::HistoryNext,
+            Kind::Visual => Step::Visual,
+            _ => Step::Bell,
+        };
+
+        if !self.insert {
+            let text = Text::new(buf.as_str());
+            let i = text.index(buf.cursor());
+            buf.set_cursor(text.offset(i.min(text.last())));
+        }
+        step
+    }
+
+    fn apply(&mut self, op: Op, cmd: Cmd, range: std::ops::Range<usize>, buf: &mut Buffer) -> Step {
+        self.register = buf.as_str()[range.clone()].to_string();
+
+        match op {
+            Op::Yank => buf.set_cursor(range.start),
+            Op::Delete => {
+                self.save(buf);
+                buf.replace(range, "");
+                self.last_change = Some((cmd, String::new()));
+            }
+            Op::Change => {
+                self.save(buf);
+                buf.replace(range, "");
+                self.start_insert(cmd);
+            }
+        }
+
+        Step::Done
+    }
+
+    fn start_insert(&mut self, cmd: Cmd) {
+        self.insert = true;
+        self.inserting = Some((cmd, String::new()));
+    }
+
+    fn save(&mut self, buf: &Buffer) {
+        self.undo.push((buf.as_str().to_string(), buf.cursor()));
+    }
+}
+
+/// Parses the keys typed so far in normal mode: `[count] command`, where an
+/// operator takes a motion with its own count, or is doubled for the line.
+fn parse(keys: &[char]) -> Parse<Cmd> {
+    let (count, keys) = parse_count(keys);
+    let Some(&c) = keys.first() else {
+        return Parse::Incomplete;
+    };
+
+    let kind = match c {
+        'd' | 'c' | 'y' => {
+            let op = match c {
+                'd' => Op::Delete,
+                'c' => Op::Change,
+                _ => Op::Yank,
+            };
+            let (inner, rest) = parse_count(&keys[1..]);
+            let count = match (count, inner) {
+                (Some(a), Some(b)) => Some(a * b),
+                (a, b) => a.or(b),
+            };
+
+            let kind = match rest {
+                [] => return Parse::Incomplete,
+                [d] if *d == c => Kind::Line(op),
+                _ => match parse_motion(rest) {
+                    Parse::Done(m) => Kind::Apply(op, m),
+                    Parse::Incomplete => return Parse::Incomplete,
+                    Parse::Invalid => return Parse::Invalid,
+                },
+            };
+            return Parse::Done(Cmd { count, kind });
+        }
+        'D' => Kind::Apply(Op::Delete, Motion::End),
+        'C' => Kind::Apply(Op::Change, Motion::End),
+        's' => Kind::Apply(Op::Change, Motion::Right),
+        'x' => Kind::DeleteChar,
+        'X' => Kind::DeleteBack,
+        'r' => match keys.get(1) {
+            Some(&c) => Kind::Replace(c),
+            None => return Parse::Incomplete,
+        },
+        'p' => Kind::Paste(false),
+        'P' => Kind::Paste(true),
+        'u' => Kind::Undo,
+        '.' => Kind::Repeat,
+        'i' => Kind::Insert(Insert::Before),
+        'a' => Kind::Insert(Insert::After),
+        'I' => Kind::Insert(Insert::Start),
+        'A' => Kind::Insert(Insert::End),
+        'k' | '-' => Kind::HistoryPrev,
+        'j' | '+' => Kind::HistoryNext,
+        'v' => Kind::Visual,
+        _ => match parse_motion(keys) {
+            Parse::Done(m) => Kind::Move(m),
+            Parse::Incomplete => return Parse::Incomplete,
+            Parse::Invalid => return Parse::Invalid,
+        },
+    };
+
+    Parse::Done(Cmd { count, kind })
+}
+
+/// Splits leading digits off `keys`. A `0` can't start a count, as it's the
+/// motion to the start of the line.
+fn parse_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    let len = match keys.first() {
+        Some('1'..='9') => keys.iter().take_while(|c| c.is_ascii_digit()).count(),
+        _ => 0,
+    };
+    let count = keys[..len].iter().collect::<String>().parse().ok();
+    (count, &keys[len..])
+}
+
+fn parse_motion(keys: &[char]) -> Parse<Motion> {
+    let motion = match keys {
+        ['h'] => Motion::Left,
+        ['l'] | [' '] => Motion::Right,
+        ['w'] => Motion::WordForward(false),
+        ['W'] => Motion::WordForward(true),
+        ['b'] => Motion::WordBack(false),
+        ['B'] => Motion::WordBack(true),
+        ['e'] => Motion::WordEnd(false),
+        ['E'] => Motion::WordEnd(true),
+        ['0'] => Motion::Start,
+        ['^'] => Motion::FirstNonBlank,
+        ['$'] => Motion::End,
+        ['f' | 't' | 'F' | 'T'] => return Parse::Incomplete,
+        [f @ ('f' | 't' | 'F' | 'T'), c] => {
+            Motion::Find(*c, matches!(f, 't' | 'T'), matches!(f, 'F' | 'T'))
+        }
+        _ => return Parse::Invalid,
+    };
+
+    Parse::Done(motion)
+}
+
+/// The line as grapheme clusters, which is what motions count in.
+struct Text<'a> {
+    graphemes: Vec<(usize, &'a str)>,
+    len: usize,
+}
+
+impl<'a> Text<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            graphemes: text.grapheme_indices(true).collect(),
+            len: text.len(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.graphemes.len()
+    }
+
+    /// The last position the cursor may rest on in normal mode.
+    fn last(&self) -> usize {
+        self.len().saturating_sub(1)
+    }
+
+    fn index(&self, offset: usize) -> usize {
+        self.graphemes.partition_point(|(o, _)| *o < offset)
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        self.graphemes.get(index).map_or(self.len, |(o, _)| *o)
+    }
+
+    fn is_blank(&self, i: usize) -> bool {
+        self.class(i, false) == 0
+    }
+
+    /// 0 for blanks, 1 for word characters and 2 for punctuation, which vi
+    /// treats as words of their own unless `big` words are asked for. Past
+    /// the end of the line counts as blank.
+    fn class(&self, i: usize, big: bool) -> u8 {
+        let c = self
+            .graphemes
+            .get(i)
+            .and_then(|(_, g)| g.chars().next())
+            .unwrap_or(' ');
+
+        if c.is_whitespace() {
+            0
+        } else if big || c.is_alphanumeric() || c == '_' {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Where `m` repeated `count` times goes from `i`, and whether the
+    /// grapheme there is part of the range it covers for an operator.
+    fn target(&self, m: Motion, i: usize, count: usize) -> Option<(usize, bool)> {
+        let n = self.len();
+
+        let target = match m {
+            Motion::Left => (i.saturating_sub(count), false),
+            Motion::Right => ((i + count).min(n), false),
+            Motion::WordForward(big) => {
+                let mut j = i;
+                for _ in 0..count {
+                    if j < n {
+                        let class = self.class(j, big);
+                        while j < n && class != 0 && self.class(j, big) == class {
+                            j += 1;
+                        }
+                    }
+                    while j < n && self.is_blank(j) {
+                        j += 1;
+                    }
+                }
+                (j, false)
+            }
+            Motion::WordBack(big) => {
+                let mut j = i;
+                for _ in 0..count {
+                    while j > 0 && self.is_blank(j - 1) {
+                        j -= 1;
+                    }
+                    if j > 0 {
+                        let class = self.class(j - 1, big);
+                        while j > 0 && self.class(j - 1, big) == class {
+                            j -= 1;
+                        }
+                    }
+                }
+                (j, false)
+            }
+            Motion::WordEnd(big) => {
+                let mut j = i;
+                for _ in 0..count {
+                    j += 1;
+                    while j < n && self.is_blank(j) {
+                        j += 1;
+                    }
+                    if j >= n {
+                        break;
+                    }
+                    let class = self.class(j, big);
+                    while j + 1 < n && self.class(j + 1, big) == class {
+                        j += 1;
+                    }
+                }
+                (j.min(self.last()), true)
+            }
+            Motion::Start => (0, false),
+            Motion::FirstNonBlank => ((0..n).find(|&j| !self.is_blank(j)).unwrap_or(n), false),
+            Motion::End => (n, false),
+            Motion::Find(c, before, back) => {
+                let mut buf = [0; 4];
+                let c = &*c.encode_utf8(&mut buf);
+                let mut j = i;
+
+                for _ in 0..count {
+                    j = if back {
+                        (0..j).rev().find(|&k| self.graphemes[k].1 == c)?
+                    } else {
+                        (j + 1..n).find(|&k| self.graphemes[k].1 == c)?
+                    };
+                }
+
+                match (before, back) {
+                    (false, false) => (j, true),
+                    (true, false) => (j - 1, true),
+                    (false, true) => (j, false),
+                    (true, true) => (j + 1, false),
+                }
+            }
+        };
+
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(vi: &mut Vi, buf: &mut Buffer, keys: &str) {
+        for c in keys.chars() {
+            let key = if c == '\x1b' {
+                Key::Escape
+            } else {
+                Key::Char(c)
+            };
+
+            if vi.handle(&key, buf) == Step::Pass {
+                buf.insert(c);
+            }
+        }
+    }
+
+    fn normal(text: &str, cursor: usize) -> (Vi, Buffer) {
+        let mut buf = Buffer::new();
+        buf.set(text);
+        buf.set_cursor(cursor);
+        let vi = Vi {
+            insert: false,
+            ..Vi::default()
+        };
+        (vi, buf)
+    }
+
+    #[test]
+    fn it_parses_commands() {
+        let parsed = |s: &str| parse(&s.chars().collect::<Vec<_>>());
+
+        assert_eq!(parsed("2"), Parse::Incomplete);
+        assert_eq!(parsed("d2"), Parse::Incomplete);
+        assert_eq!(parsed("f"), Parse::Incomplete);
+        assert_eq!(parsed("dq"), Parse::Invalid);
+        assert_eq!(
+            parsed("2d3w"),
+            Parse::Done(Cmd {
+                count: Some(6),
+                kind: Kind::Apply(Op::Delete, Motion::WordForward(false)),
+            })
+        );
+        assert_eq!(
+            parsed("0"),
+            Parse::Done(Cmd {
+                count: None,
+                kind: Kind::Move(Motion::Start),
+            })
+        );
+        assert_eq!(
+            parsed("10x"),
+            Parse::Done(Cmd {
+                count: Some(10),
+                kind: Kind::DeleteChar,
+            })
+        );
+    }
+
+    #[test]
+    fn it_moves() {
+        let (mut vi, mut buf) = normal("git commit -m 'fix it'", 0);
+        keys(&mut vi, &mut buf, "w");
+        assert_eq!(buf.cursor(), 4);
+        keys(&mut vi, &mut buf, "2w");
+        assert_eq!(buf.cursor(), 12);
+        keys(&mut vi, &mut buf, "e");
+        assert_eq!(buf.cursor(), 14);
+        keys(&mut vi, &mut buf, "b");
+        assert_eq!(buf.cursor(), 12);
+        keys(&mut vi, &mut buf, "$");
+        assert_eq!(buf.cursor(), 21);
+        keys(&mut vi, &mut buf, "0fi");
+        assert_eq!(buf.cursor(), 1);
+        keys(&mut vi, &mut buf, "2ti");
+        assert_eq!(buf.cursor(), 15);
+        keys(&mut vi, &mut buf, "Fm");
+        assert_eq!(buf.cursor(), 12);
+        keys(&mut vi, &mut buf, "3h");
+        assert_eq!(buf.cursor(), 9);
+    }
+
+    #[test]
+    fn it_applies_operators() {
+        let (mut vi, mut buf) = normal("echo one two three", 5);
+        keys(&mut vi, &mut buf, "dw");
+        assert_eq!(buf.as_str(), "echo two three");
+        keys(&mut vi, &mut buf, "cwsix\x1b");
+        assert_eq!(buf.as_str(), "echo six three");
+        assert_eq!(buf.cursor(), 7);
+        keys(&mut vi, &mut buf, "w.");
+        assert_eq!(buf.as_str(), "echo six six");
+        keys(&mut vi, &mut buf, "0yeP");
+        assert_eq!(buf.as_str(), "echoecho six six");
+        keys(&mut vi, &mut buf, "u");
+        assert_eq!(buf.as_str(), "echo six six");
+        keys(&mut vi, &mut buf, "uuu");
+        assert_eq!(buf.as_str(), "echo one two three");
+        keys(&mut vi, &mut buf, "$d0");
+        assert_eq!(buf.as_str(), "e");
+        keys(&mut vi, &mut buf, "dd");
+        assert_eq!(buf.as_str(), "");
+    }
+
+    #[test]
+    fn it_edits_characters() {
+        let (mut vi, mut buf) = normal("ls -la", 3);
+        keys(&mut vi, &mut buf, "2x");
+        assert_eq!(buf.as_str(), "ls a");
+        keys(&mut vi, &mut buf, "p");
+        assert_eq!(buf.as_str(), "ls a-l");
+        keys(&mut vi, &mut buf, "0rL.");
+        assert_eq!(buf.as_str(), "Ls a-l");
+        keys(&mut vi, &mut buf, "A -h\x1b");
+        assert_eq!(buf.as_str(), "Ls a-l -h");
+        keys(&mut vi, &mut buf, "0i#\x1b");
+        assert_eq!(buf.as_str(), "#Ls a-l -h");
+        assert_eq!(buf.cursor(), 0);
+    }
+
+    #[test]
+    fn it_edits_an_empty_line() {
+        let (mut vi, mut buf) = normal("", 0);
+        keys(&mut vi, &mut buf, "x");
+        assert_eq!(buf.as_str(), "");
+        keys(&mut vi, &mut buf, "dw");
+        assert_eq!(buf.as_str(), "");
+        keys(&mut vi, &mut buf, "cwls\x1b");
+        assert_eq!(buf.as_str(), "ls");
+    }
+}
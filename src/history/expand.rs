@@ -0,0 +1,422 @@
+/// A line after history expansion.
+#[derive(Debug, PartialEq)]
+pub struct Expanded {
+    pub line: String,
+    /// Whether any expansion took place, in which case the line is echoed.
+    pub changed: bool,
+    /// The `:p` modifier: show the line without running it.
+    pub print_only: bool,
+}
+
+/// Performs csh-style history expansion (`!!`, `!n`, `!-n`, `!prefix`,
+/// `!?substring?`, `!$`, `^old^new^`) against the current history.
+pub fn expand(line: &str) -> Result<Expanded, String> {
+    let history = super::lock();
+    expand_with(line, &history.entries, history.base)
+}
+
+/// Expands `line` against `entries`, the first of which is numbered `base + 1`.
+fn expand_with(line: &str, entries: &[String], base: usize) -> Result<Expanded, String> {
+    let mut expanded = Expanded {
+        line: String::new(),
+        changed: false,
+        print_only: false,
+    };
+
+    let line = match line.strip_prefix('^') {
+        Some(rest) => {
+            expanded.changed = true;
+            quick_substitution(rest, entries)?
+        }
+        None => line.to_string(),
+    };
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut single = false;
+    let mut double = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        match c {
+            '\\' if !single && i + 1 < chars.len() => {
+                expanded.line.push(c);
+                expanded.line.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '!' if !single => {
+                if let Some((text, end, print)) = event(&chars, i, entries, base)? {
+                    expanded.line.push_str(&text);
+                    expanded.changed = true;
+                    expanded.print_only |= print;
+                    i = end;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        expanded.line.push(c);
+        i += 1;
+    }
+
+    Ok(expanded)
+}
+
+/// `^old^new^rest`: the previous command with the first `old` replaced.
+fn quick_substitution(spec: &str, entries: &[String]) -> Result<String, String> {
+    let mut parts = spec.splitn(3, '^');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+    let rest = parts.next().unwrap_or_default();
+
+    let prev = entries.last().ok_or("!!: event not found")?;
+
+    if old.is_empty() || !prev.contains(old) {
+        return Err(format!("^{spec}: substitution failed"));
+    }
+
+    Ok(format!("{}{rest}", prev.replacen(old, new, 1)))
+}
+
+/// Expands the history reference starting at `chars[start]`, a `!`. Returns
+/// the text, the index right after the reference and whether `:p` was given,
+/// or `None` when the `!` is to be taken literally.
+fn event(
+    chars: &[char],
+    start: usize,
+    entries: &[String],
+    base: usize,
+) -> Result<Option<(String, usize, bool)>, String> {
+    let mut i = start + 1;
+    let last = || entries.last().cloned();
+
+    let line = match chars.get(i) {
+        None | Some(' ' | '\t' | '\n' | '=' | '(' | '"') => return Ok(None),
+        Some('!') => {
+            i += 1;
+            last()
+        }
+        Some('$' | '^' | '*' | ':') => last(),
+        Some('-') | Some('0'..='9') => {
+            let negative = chars[i] == '-';
+            if negative {
+                i += 1;
+            }
+            let digits = take_while(chars, &mut i, |c| c.is_ascii_digit());
+            let num: usize = digits
+                .parse()
+                .map_err(|_| format!("!{digits}: event not found"))?;
+
+            let index = if negative {
+                entries.len().checked_sub(num)
+            } else {
+                num.checked_sub(base + 1)
+            };
+            index.and_then(|index| entries.get(index).cloned())
+        }
+        Some('?') => {
+            i += 1;
+            let needle = take_while(chars, &mut i, |c| c != '?' && c != '\n');
+            if chars.get(i) == Some(&'?') {
+                i += 1;
+            }
+            entries.iter().rev().find(|e| e.contains(&needle)).cloned()
+        }
+        Some(_) => {
+            let prefix = take_while(chars, &mut i, |c| {
+                !c.is_whitespace() && !matches!(c, ':' | '\'' | '"' | ';' | '&' | '|')
+            });
+            // Nothing that could start a prefix follows, as in `hi!|cat`.
+            if prefix.is_empty() {
+                return Ok(None);
+            }
+            entries
+                .iter()
+                .rev()
+                .find(|e| e.starts_with(&prefix))
+                .cloned()
+        }
+    };
+
+    let reference: String = chars[start..i].iter().collect();
+    let line = line.ok_or(format!("{reference}: event not found"))?;
+
+    let mut text = line.clone();
+
+    // `!$`, `!^` and `!*` take the designator without a colon.
+    if matches!(chars.get(i), Some('$' | '^' | '*')) && i == start + 1 {
+        let (from, to) = designator(chars, &mut i, &line)?;
+        text = select(&line, from, to)?;
+    } else if chars.get(i) == Some(&':')
+        && matches!(chars.get(i + 1), Some('0'..='9' | '^' | '$' | '*' | '-'))
+    {
+        i += 1;
+        let (from, to) = designator(chars, &mut i, &line)?;
+        text = select(&line, from, to)?;
+    }
+
+    let mut print = false;
+
+    while chars.get(i) == Some(&':') {
+        match chars.get(i + 1) {
+            Some('h') => text = head(&text),
+            Some('t') => text = tail(&text),
+            Some('r') => text = root(&text),
+            Some('e') => text = extension(&text),
+            Some('p') => print = true,
+            Some('s') => {
+                i += 2;
+                text = substitute(chars, &mut i, &text)?;
+                continue;
+            }
+            _ => break,
+        }
+        i += 2;
+    }
+
+    Ok(Some((text, i, print)))
+}
+
+/// Parses a word designator into an inclusive range of word indices, where
+/// `None` stands for the last word.
+fn designator(chars: &[char], i: &mut usize, line: &str) -> Result<(usize, Option<usize>), String> {
+    let last = words(line).len().saturating_sub(1);
+
+    let number = |i: &mut usize| -> Option<usize> {
+        take_while(chars, i, |c| c.is_ascii_digit()).parse().ok()
+    };
+
+    let from = match chars.get(*i) {
+        Some('^') => {
+            *i += 1;
+            1
+        }
+        Some('$') => {
+            *i += 1;
+            return Ok((last, Some(last)));
+        }
+        Some('*') => {
+            *i += 1;
+            return Ok((1, None));
+        }
+        Some('-') => 0,
+        _ => number(i).ok_or("bad word specifier")?,
+    };
+
+    match chars.get(*i) {
+        Some('*') => {
+            *i += 1;
+            Ok((from, None))
+        }
+        Some('-') => {
+            *i += 1;
+            match chars.get(*i) {
+                Some('$') => {
+                    *i += 1;
+                    Ok((from, Some(last)))
+                }
+                Some(c) if c.is_ascii_digit() => Ok((from, number(i))),
+                _ => Ok((from, Some(last.saturating_sub(1)))),
+            }
+        }
+        _ => Ok((from, Some(from))),
+    }
+}
+
+fn select(line: &str, from: usize, to: Option<usize>) -> Result<String, String> {
+    let words = words(line);
+
+    if words.is_empty() {
+        return Ok(String::new());
+    }
+
+    let to = to.unwrap_or(words.len() - 1);
+
+    // `!*` on a command without arguments expands to nothing.
+    if from == words.len() && to == words.len() - 1 {
+        return Ok(String::new());
+    }
+
+    if from > to || to >= words.len() {
+        return Err("bad word specifier".into());
+    }
+
+    Ok(words[from..=to].join(" "))
+}
+
+/// Splits a command line into words on unquoted whitespace, keeping quotes.
+fn words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', None) => {
+                word.push(c);
+                word.extend(chars.next());
+                continue;
+            }
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// `:s/old/new/`, where any character can stand in for `/` and `&` in `new`
+/// stands for `old`. `i` points right after the `s`.
+fn substitute(chars: &[char], i: &mut usize, text: &str) -> Result<String, String> {
+    let delim = *chars.get(*i).ok_or("bad substitution")?;
+    *i += 1;
+
+    let old = take_while(chars, i, |c| c != delim);
+    *i += 1;
+    let new = take_while(chars, i, |c| c != delim);
+    if chars.get(*i) == Some(&delim) {
+        *i += 1;
+    }
+
+    if old.is_empty() || !text.contains(&old) {
+        return Err(format!(
+            ":s{delim}{old}{delim}{new}{delim}: substitution failed"
+        ));
+    }
+
+    Ok(text.replacen(&old, &new.replace('&', &old), 1))
+}
+
+fn head(text: &str) -> String {
+    match text.rsplit_once('/') {
+        Some((head, _)) => head.to_string(),
+        None => text.to_string(),
+    }
+}
+
+fn tail(text: &str) -> String {
+    match text.rsplit_once('/') {
+        Some((_, tail)) => tail.to_string(),
+        None => text.to_string(),
+    }
+}
+
+fn root(text: &str) -> String {
+    match text.rfind('.') {
+        Some(dot) if !text[dot..].contains('/') => text[..dot].to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn extension(text: &str) -> String {
+    match text.rfind('.') {
+        Some(dot) if !text[dot..].contains('/') => text[dot..].to_string(),
+        _ => String::new(),
+    }
+}
+
+fn take_while(chars: &[char], i: &mut usize, f: impl Fn(char) -> bool) -> String {
+    let start = *i;
+    while *i < chars.len() && f(chars[*i]) {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        vec![
+            "ls -la src".into(),
+            "vim src/main.rs".into(),
+            "cat 'a b.txt' foo.tar.gz".into(),
+        ]
+    }
+
+    fn expand(line: &str) -> Result<String, String> {
+        expand_with(line, &history(), 10).map(|e| e.line)
+    }
+
+    #[test]
+    fn it_expands_events() {
+        assert_eq!(
+            expand("sudo !!"),
+            Ok("sudo cat 'a b.txt' foo.tar.gz".into())
+        );
+        assert_eq!(expand("!11"), Ok("ls -la src".into()));
+        assert_eq!(expand("!-2"), Ok("vim src/main.rs".into()));
+        assert_eq!(expand("!vi"), Ok("vim src/main.rs".into()));
+        assert_eq!(expand("!?main?"), Ok("vim src/main.rs".into()));
+        assert_eq!(expand("!foo"), Err("!foo: event not found".into()));
+        assert_eq!(expand("!1"), Err("!1: event not found".into()));
+    }
+
+    #[test]
+    fn it_expands_word_designators() {
+        assert_eq!(expand("vim !$"), Ok("vim foo.tar.gz".into()));
+        assert_eq!(expand("echo !^"), Ok("echo 'a b.txt'".into()));
+        assert_eq!(expand("echo !*"), Ok("echo 'a b.txt' foo.tar.gz".into()));
+        assert_eq!(expand("echo !ls:1"), Ok("echo -la".into()));
+        assert_eq!(expand("echo !ls:0-1"), Ok("echo ls -la".into()));
+        assert_eq!(expand("echo !ls:1-"), Ok("echo -la".into()));
+        assert_eq!(expand("echo !ls:5"), Err("bad word specifier".into()));
+    }
+
+    #[test]
+    fn it_applies_modifiers() {
+        assert_eq!(expand("echo !vim:$:h"), Ok("echo src".into()));
+        assert_eq!(expand("echo !vim:$:t"), Ok("echo main.rs".into()));
+        assert_eq!(expand("echo !vim:$:r"), Ok("echo src/main".into()));
+        assert_eq!(expand("echo !$:e"), Ok("echo .gz".into()));
+        assert_eq!(expand("!vim:s/main/lib/"), Ok("vim src/lib.rs".into()));
+        assert_eq!(
+            expand("!vim:s/x/y/"),
+            Err(":s/x/y/: substitution failed".into())
+        );
+
+        let expanded = expand_with("!ls:p", &history(), 0).unwrap();
+        assert!(expanded.print_only);
+        assert_eq!(expanded.line, "ls -la src");
+    }
+
+    #[test]
+    fn it_substitutes_quickly() {
+        assert_eq!(expand("^foo^bar"), Ok("cat 'a b.txt' bar.tar.gz".into()));
+        assert_eq!(
+            expand("^zzz^bar"),
+            Err("^zzz^bar: substitution failed".into())
+        );
+    }
+
+    #[test]
+    fn it_leaves_literal_bangs_alone() {
+        let expanded = expand_with("echo '!!' \\!! a ! b!=c", &history(), 0).unwrap();
+        assert_eq!(expanded.line, "echo '!!' \\!! a ! b!=c");
+        assert!(!expanded.changed);
+
+        let line = "echo hi!|cat !;x !&y !'z'";
+        let expanded = expand_with(line, &history(), 0).unwrap();
+        assert_eq!(expanded.line, line);
+        assert!(!expanded.changed);
+    }
+}
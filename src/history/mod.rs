@@ -0,0 +1,389 @@
+use crate::Result;
+
+mod expand;
+
+pub use expand::expand;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const DEFAULT_SIZE: usize = 1000;
+
+static HISTORY: Mutex<History> = Mutex::new(History::new());
+
+#[derive(Debug, Default, PartialEq)]
+pub struct History {
+    entries: Vec<String>,
+    /// Number of the entry before the first one kept, so that numbers stay
+    /// stable when old entries are dropped.
+    base: usize,
+    /// Index of the first entry not yet written to the history file.
+    appended: usize,
+    /// Only interactive shells record and save history.
+    enabled: bool,
+}
+
+impl History {
+    const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            base: 0,
+            appended: 0,
+            enabled: false,
+        }
+    }
+
+    fn push(&mut self, line: String, limit: Option<usize>) {
+        self.entries.push(line);
+        self.truncate(limit);
+    }
+
+    /// Adds lines read from a file before the entries not appended yet, and
+    /// counts them as appended, so that they aren't written back on exit
+    /// while the ones typed in this session still are.
+    fn read(&mut self, lines: Vec<String>, limit: Option<usize>) {
+        let unsaved = self.entries.split_off(self.appended);
+        self.entries.extend(lines);
+        self.appended = self.entries.len();
+        self.entries.extend(unsaved);
+        self.truncate(limit);
+    }
+
+    fn truncate(&mut self, limit: Option<usize>) {
+        if let Some(limit) = limit {
+            let excess = self.entries.len().saturating_sub(limit);
+            self.entries.drain(..excess);
+            self.base += excess;
+            self.appended = self.appended.saturating_sub(excess);
+        }
+    }
+}
+
+/// Walks through the history with the arrow keys, keeping the line being
+/// typed so that it can be brought back.
+#[derive(Debug, Default)]
+pub struct Navigator {
+    pos: Option<usize>,
+    draft: String,
+}
+
+impl Navigator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves to the previous entry. `current` is the line as typed so far.
+    pub fn prev(&mut self, current: &str) -> Option<String> {
+        let history = lock();
+        let pos = match self.pos {
+            Some(0) => return None,
+            Some(pos) => pos - 1,
+            None => {
+                self.draft = current.to_string();
+                history.entries.len().checked_sub(1)?
+            }
+        };
+
+        self.pos = Some(pos);
+        history.entries.get(pos).cloned()
+    }
+
+    /// Moves to the next entry, back to the typed line past the last one.
+    pub fn next(&mut self) -> Option<String> {
+        let history = lock();
+        let pos = self.pos? + 1;
+
+        if pos < history.entries.len() {
+            self.pos = Some(pos);
+            history.entries.get(pos).cloned()
+        } else {
+            self.pos = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+}
+
+/// Turns history on and loads `$HISTFILE`.
+pub fn init() {
+    lock().enabled = true;
+
+    if let Some(path) = file() {
+        if let Err(err) = read(&path) {
+            if !matches!(err, crate::Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound) {
+                eprintln!("history: {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Records a line typed at the prompt.
+pub fn push(line: &str) {
+    let mut history = lock();
+
+    if history.enabled && !line.trim().is_empty() {
+        history.push(line.to_string(), size());
+    }
+}
+
+pub fn len() -> usize {
+    lock().entries.len()
+}
+
+/// Returns `(number, line)` pairs for the last `count` entries, or all of them.
+pub fn entries(count: Option<usize>) -> Vec<(usize, String)> {
+    let history = lock();
+    let skip = count.map_or(0, |n| history.entries.len().saturating_sub(n));
+
+    history
+        .entries
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .map(|(i, line)| (history.base + i + 1, line.clone()))
+        .collect()
+}
+
+/// Finds the most recent entry before index `before` containing `query`.
+pub fn search(query: &str, before: usize) -> Option<(usize, String)> {
+    rfind(&lock().entries, query, before)
+}
+
+fn rfind(entries: &[String], query: &str, before: usize) -> Option<(usize, String)> {
+    entries[..before.min(entries.len())]
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, line)| line.contains(query))
+        .map(|(i, line)| (i, line.clone()))
+}
+
+pub fn clear() {
+    let mut history = lock();
+    history.base += history.entries.len();
+    history.entries.clear();
+    history.appended = 0;
+}
+
+/// Deletes the entry numbered `num`; negative numbers count from the end.
+pub fn delete(num: i64) -> bool {
+    let mut history = lock();
+    let len = history.entries.len() as i64;
+
+    let index = if num < 0 {
+        len + num
+    } else {
+        num - history.base as i64 - 1
+    };
+
+    if index < 0 || index >= len {
+        return false;
+    }
+
+    let index = index as usize;
+    history.entries.remove(index);
+    if index < history.appended {
+        history.appended -= 1;
+    }
+    true
+}
+
+/// Adds the lines of `path` to the history, ahead of this session's
+/// entries that haven't been appended yet.
+pub fn read(path: &Path) -> Result<()> {
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+
+    let lines = content
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect();
+    lock().read(lines, size());
+    Ok(())
+}
+
+/// Overwrites `path` with the whole history.
+pub fn write(path: &Path) -> Result<()> {
+    let mut history = lock();
+    let lines = history.entries.clone();
+
+    with_locked(path, |file| {
+        file.set_len(0)?;
+        write_lines(file, &lines)
+    })?;
+
+    history.appended = history.entries.len();
+    Ok(())
+}
+
+/// Appends the entries added since the last append (or since the history
+/// file was loaded) to `path`. The file is locked meanwhile, so concurrent
+/// sessions interleave whole batches instead of corrupting each other.
+pub fn append(path: &Path) -> Result<()> {
+    let mut history = lock();
+    let lines = history.entries[history.appended..].to_vec();
+
+    with_locked(path, |file| write_lines(file, &lines))?;
+
+    history.appended = history.entries.len();
+    Ok(())
+}
+
+/// Appends this session's new entries to `$HISTFILE` when the shell exits.
+pub fn save() {
+    if !lock().enabled {
+        return;
+    }
+
+    if let Some(path) = file() {
+        if let Err(err) = append(&path) {
+            eprintln!("history: {}: {err}", path.display());
+        }
+    }
+}
+
+pub fn file() -> Option<PathBuf> {
+    match std::env::var("HISTFILE") {
+        Ok(path) if path.is_empty() => None,
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => std::env::var("HOME")
+            .ok()
+            .map(|home| Path::new(&home).join(".shell_history")),
+    }
+}
+
+/// `$HISTSIZE`, the number of entries kept in memory. `None` means unlimited.
+fn size() -> Option<usize> {
+    limit("HISTSIZE", Some(DEFAULT_SIZE))
+}
+
+/// `$HISTFILESIZE`, the number of lines kept in the history file.
+fn file_size() -> Option<usize> {
+    limit("HISTFILESIZE", size())
+}
+
+/// Reads a size variable: unset uses `default`, a negative or non-numeric
+/// value means no limit.
+fn limit(var: &str, default: Option<usize>) -> Option<usize> {
+    match std::env::var(var) {
+        Ok(value) => value.trim().parse::<usize>().ok(),
+        Err(_) => default,
+    }
+}
+
+/// Opens `path` for appending under an exclusive lock, runs `f`, then trims
+/// the file to `$HISTFILESIZE` lines before releasing the lock.
+fn with_locked(path: &Path, f: impl FnOnce(&mut File) -> io::Result<()>) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    f(&mut file)?;
+
+    if let Some(limit) = file_size() {
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut content)?;
+
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.len() > limit {
+            let keep: Vec<String> = lines[(lines.len() - limit)..]
+                .iter()
+                .map(|l| l.to_string())
+                .collect();
+            file.set_len(0)?;
+            write_lines(&mut file, &keep)?;
+        }
+    }
+
+    // Closing the file releases the lock.
+    Ok(())
+}
+
+fn write_lines(file: &mut File, lines: &[String]) -> io::Result<()> {
+    let mut buf = String::new();
+    for line in lines {
+        buf.push_str(line);
+        buf.push('\n');
+    }
+    file.write_all(buf.as_bytes())
+}
+
+fn lock() -> std::sync::MutexGuard<'static, History> {
+    HISTORY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_searches_backwards() {
+        let entries: Vec<String> = vec!["git status".into(), "ls".into(), "git push".into()];
+
+        assert_eq!(rfind(&entries, "git", 3), Some((2, "git push".into())));
+        assert_eq!(rfind(&entries, "git", 2), Some((0, "git status".into())));
+        assert_eq!(rfind(&entries, "git", 0), None);
+        assert_eq!(rfind(&entries, "", 10), Some((2, "git push".into())));
+        assert_eq!(rfind(&entries, "cargo", 3), None);
+    }
+
+    #[test]
+    fn it_drops_old_entries() {
+        let mut history = History::new();
+        history.push("a".into(), Some(2));
+        history.push("b".into(), Some(2));
+        history.appended = 2;
+        history.push("c".into(), Some(2));
+
+        assert_eq!(history.entries, vec!["b", "c"]);
+        assert_eq!(history.base, 1);
+        assert_eq!(history.appended, 1);
+
+        history.truncate(None);
+        assert_eq!(history.entries, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn it_reads_ahead_of_unsaved_entries() {
+        let mut history = History::new();
+        history.push("a".into(), None);
+        history.appended = 1;
+        history.push("b".into(), None);
+        history.read(vec!["x".into(), "y".into()], Some(3));
+
+        assert_eq!(history.entries, vec!["x", "y", "b"]);
+        assert_eq!(history.appended, 2);
+    }
+
+    #[test]
+    fn it_saves_what_was_typed_but_not_what_was_read() {
+        let histfile = tempfile::NamedTempFile::new().unwrap();
+        let other = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(histfile.path(), "ls\n").unwrap();
+        std::fs::write(other.path(), "cd /tmp\n").unwrap();
+        std::env::set_var("HISTFILE", histfile.path());
+        lock().enabled = true;
+
+        push("echo typed");
+        push("history -r");
+        read(other.path()).unwrap();
+        read(histfile.path()).unwrap();
+        push("echo after");
+        save();
+
+        assert_eq!(
+            std::fs::read_to_string(histfile.path()).unwrap(),
+            "ls\necho typed\nhistory -r\necho after\n"
+        );
+    }
+}
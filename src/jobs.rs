@@ -0,0 +1,281 @@
+use crate::{signal, Result};
+
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::Mutex;
+
+static JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: i32,
+    pub cmd: String,
+    pub state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Running,
+    Stopped,
+    Done(i32),
+    Signaled(i32),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Exited(i32),
+    Signaled(i32),
+    /// The job, and the signal that stopped it.
+    Stopped(Job, i32),
+}
+
+impl Status {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Exited(code) => *code,
+            Self::Signaled(sig) => 128 + sig,
+            Self::Stopped(_, sig) => 128 + sig,
+        }
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}]+  {:<24}{}",
+            self.id,
+            self.state.to_string(),
+            self.cmd
+        )
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running => write!(f, "Running"),
+            Self::Stopped => write!(f, "Stopped"),
+            Self::Done(0) => write!(f, "Done"),
+            Self::Done(code) => write!(f, "Exit {code}"),
+            Self::Signaled(sig) => write!(f, "{}", signal::describe(*sig)),
+        }
+    }
+}
+
+/// Runs `cmd` in its own process group owning the terminal and waits until it
+/// exits, is killed or is stopped. Stopped jobs are remembered so they can be
+/// signalled later.
+///
+/// The program is executed with `execv`, so it has to be a path: there is no
+/// `$PATH` lookup, and unlike `execvp` a file the kernel cannot execute fails
+/// with `ENOEXEC` instead of being handed to `/bin/sh`.
+pub fn run_foreground(cmd: &mut Command, line: &str) -> Result<Status> {
+    let program = CString::new(cmd.get_program().as_bytes()).map_err(io::Error::from)?;
+    let args = std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| CString::new(arg.as_bytes()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(io::Error::from)?;
+    let argv = Argv(
+        args.iter()
+            .map(|arg| arg.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect(),
+    );
+
+    // Only a shell with the terminal puts jobs in groups of their own to hand
+    // it to. Otherwise they stay in the shell's group, so that reading the
+    // terminal doesn't stop them and Ctrl-C reaches them along with the shell.
+    let interactive = signal::is_interactive();
+
+    unsafe {
+        cmd.pre_exec(move || {
+            // The child takes the terminal itself, as the parent only gets to
+            // once the program runs, which may read it straight away.
+            // SIGTTOU is still ignored here, so asking for it doesn't stop us.
+            if interactive {
+                libc::setpgid(0, 0);
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+            }
+            signal::restore_defaults();
+            // Keep `args` alive in the child, `argv` points into it.
+            let _ = &args;
+            libc::execv(program.as_ptr(), argv.as_ptr());
+            Err(io::Error::last_os_error())
+        });
+    }
+
+    let child = cmd.spawn()?;
+    let pid = child.id() as i32;
+
+    signal::set_foreground(pid);
+    let status = wait(pid);
+    signal::reclaim_terminal();
+
+    let status = status?;
+
+    if libc::WIFSTOPPED(status) {
+        Ok(Status::Stopped(push(pid, line), libc::WSTOPSIG(status)))
+    } else if libc::WIFSIGNALED(status) {
+        Ok(Status::Signaled(libc::WTERMSIG(status)))
+    } else {
+        Ok(Status::Exited(libc::WEXITSTATUS(status)))
+    }
+}
+
+/// Pointers into `CString`s owned by the same `pre_exec` closure.
+struct Argv(Vec<*const libc::c_char>);
+
+impl Argv {
+    fn as_ptr(&self) -> *const *const libc::c_char {
+        self.0.as_ptr()
+    }
+}
+
+unsafe impl Send for Argv {}
+unsafe impl Sync for Argv {}
+
+fn wait(pid: i32) -> Result<i32> {
+    let mut status = 0;
+
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } >= 0 {
+            return Ok(status);
+        }
+
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+}
+
+/// Resolves a job spec: `%n`, `%%`, `%+`, `%-`, `%prefix` or `%?substring`.
+pub fn find(spec: &str) -> Option<Job> {
+    let spec = spec.strip_prefix('%')?;
+    let jobs = lock();
+
+    let job = match spec {
+        "" | "%" | "+" => jobs.last(),
+        "-" => jobs.iter().rev().nth(1).or(jobs.last()),
+        _ => {
+            if let Ok(id) = spec.parse::<usize>() {
+                jobs.iter().find(|j| j.id == id)
+            } else if let Some(s) = spec.strip_prefix('?') {
+                jobs.iter().rev().find(|j| j.cmd.contains(s))
+            } else {
+                jobs.iter().rev().find(|j| j.cmd.starts_with(spec))
+            }
+        }
+    };
+
+    job.cloned()
+}
+
+/// Polls every job without blocking and returns the ones that finished since
+/// the last call. Finished jobs are forgotten, stopped/continued ones updated.
+pub fn reap() -> Vec<Job> {
+    let mut finished = vec![];
+
+    lock().retain_mut(|job| {
+        let mut status = 0;
+        let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+        let pid = unsafe { libc::waitpid(-job.pgid, &mut status, flags) };
+
+        if pid == 0 {
+            return true;
+        } else if pid < 0 {
+            return false;
+        }
+
+        if libc::WIFSTOPPED(status) {
+            job.state = State::Stopped;
+            true
+        } else if libc::WIFCONTINUED(status) {
+            job.state = State::Running;
+            true
+        } else {
+            job.state = if libc::WIFSIGNALED(status) {
+                State::Signaled(libc::WTERMSIG(status))
+            } else {
+                State::Done(libc::WEXITSTATUS(status))
+            };
+            finished.push(job.clone());
+            false
+        }
+    });
+
+    finished
+}
+
+fn push(pgid: i32, cmd: &str) -> Job {
+    let mut jobs = lock();
+    let id = jobs.iter().map(|j| j.id).max().unwrap_or_default() + 1;
+    let job = Job {
+        id,
+        pgid,
+        cmd: cmd.to_string(),
+        state: State::Stopped,
+    };
+    jobs.push(job.clone());
+    job
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Vec<Job>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_waits_for_the_foreground_job() {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "exit 3"]);
+        let status = run_foreground(&mut cmd, "sh -c 'exit 3'").unwrap();
+        assert_eq!(status, Status::Exited(3));
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "kill -TERM $$"]);
+        let status = run_foreground(&mut cmd, "sh -c 'kill -TERM $$'").unwrap();
+        assert_eq!(status, Status::Signaled(libc::SIGTERM));
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "kill -STOP $$"]);
+        let status = run_foreground(&mut cmd, "sh -c 'kill -STOP $$'").unwrap();
+        assert_eq!(status.code(), 128 + libc::SIGSTOP);
+
+        let Status::Stopped(job, _) = status else {
+            panic!("not stopped: {status:?}");
+        };
+        unsafe {
+            libc::kill(job.pgid, libc::SIGKILL);
+            libc::waitpid(job.pgid, std::ptr::null_mut(), 0);
+        }
+        lock().retain(|j| j.id != job.id);
+    }
+
+    #[test]
+    fn it_displays_jobs() {
+        let job = Job {
+            id: 2,
+            pgid: 100,
+            cmd: "sleep 10".into(),
+            state: State::Stopped,
+        };
+        assert_eq!(job.to_string(), "[2]+  Stopped                 sleep 10");
+
+        let job = Job {
+            state: State::Done(3),
+            ..job
+        };
+        assert_eq!(job.to_string(), "[2]+  Exit 3                  sleep 10");
+    }
+}
@@ -1,102 +1,277 @@
-use std::io::Write;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
 
 #[macro_use]
 mod macros;
 
 mod cmd;
+mod complete;
+mod editor;
 mod error;
+mod history;
+mod jobs;
+mod options;
 mod parser;
+mod signal;
+mod startup;
+mod trap;
+mod vars;
 mod writer;
 
 pub type Result<T> = std::result::Result<T, Error>;
 pub use error::Error;
+pub use options::{Mode, Options};
 
+use cmd::function::{self, Definition};
 use cmd::Command;
-use console::{Key, Term};
-use parser::Inputs;
+use console::Term;
+use editor::{Editor, Line};
+use parser::{Assignment, Inputs};
+use writer::Writer;
 
-pub fn repl(f: impl Fn(&str) -> Result<()>) -> Result<()> {
+/// Runs the shell as described by its command line and returns the status it
+/// should exit with.
+pub fn run(opts: Options) -> Result<i32> {
+    vars::set_name(opts.name);
+    vars::set_positional(opts.args);
+
+    // Take the terminal before the startup files run, so that the programs
+    // they start get it, and traps they set aren't undone afterwards.
+    let interactive = opts.mode == Mode::Stdin && io::stdin().is_terminal();
+    if interactive {
+        signal::init();
+    }
+    startup::run(&opts.startup, interactive);
+
+    match opts.mode {
+        Mode::Command(cmds) => run_lines(cmds.as_bytes(), exec_cmd),
+        Mode::Script(path) => match File::open(&path) {
+            Ok(file) => run_lines(BufReader::new(file), exec_cmd),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                eprintln!("{}: No such file or directory", path.display());
+                Ok(127)
+            }
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                Ok(126)
+            }
+        },
+        Mode::Stdin if !io::stdin().is_terminal() => run_lines(io::stdin().lock(), exec_cmd),
+        Mode::Stdin => repl(exec_cmd),
+    }
+}
+
+/// Runs every command of `reader` and returns the status of the last one.
+pub fn run_lines(reader: impl BufRead, f: impl Fn(&str) -> Result<i32>) -> Result<i32> {
+    let mut status = 0;
+
+    for command in commands(reader) {
+        let (_, command) = command?;
+        status = f(&command)?;
+    }
+
+    Ok(status)
+}
+
+/// Reads the lines of `reader` as commands, along with the number of the line
+/// each starts on. Blank lines and comments are skipped, and a function
+/// definition spanning lines is a single command.
+fn commands(reader: impl BufRead) -> impl Iterator<Item = io::Result<(usize, String)>> {
+    let mut lines = reader.lines().enumerate();
+
+    std::iter::from_fn(move || loop {
+        let (i, line) = lines.next()?;
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut command = line.to_string();
+        let mut depth = usize::from(function::opens(line));
+
+        while depth > 0 {
+            let Some((_, line)) = lines.next() else {
+                break;
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            let line = line.trim();
+
+            if function::opens(line) {
+                depth += 1;
+            } else if line == "}" {
+                depth -= 1;
+            }
+            command.push('\n');
+            command.push_str(line);
+        }
+
+        return Some(Ok((i + 1, command)));
+    })
+}
+
+/// Runs the commands in the file at `path` in this shell and returns the
+/// status of the last one. Errors are reported with the file and line they
+/// came from, and the commands after them still run.
+pub fn source(path: &Path) -> Result<i32> {
+    let file = File::open(path)?;
+    let mut status = 0;
+
+    for command in commands(BufReader::new(file)) {
+        let (line, command) = command?;
+        let location = format!("{}: line {line}", path.display());
+
+        let outer = writer::set_location(Some(location.clone()));
+        let result = exec_cmd(&command);
+        writer::set_location(outer);
+
+        status = match result {
+            Ok(status) => status,
+            Err(err) => {
+                eprintln!("{location}: {err}");
+                1
+            }
+        };
+    }
+
+    Ok(status)
+}
+
+/// Runs the EXIT trap, saves the history and terminates the shell.
+pub fn exit(code: i32) -> ! {
+    trap::run_exit();
+    history::save();
+    std::process::exit(code);
+}
+
+pub fn repl(f: impl Fn(&str) -> Result<i32>) -> Result<i32> {
     let mut term = Term::stdout();
+    let mut editor = Editor::new();
+    history::init();
+    let mut eofs = 0;
 
     loop {
-        write!(term, "$ ")?;
+        for job in jobs::reap() {
+            writeln!(term, "{job}")?;
+        }
 
-        let mut buf: Vec<u8> = vec![];
-        let mut candidates: Vec<String> = vec![];
+        let line = match editor.read_line("$ ")? {
+            Line::Input(line) => line,
+            Line::Interrupted => {
+                trap::run(trap::Condition::Signal(libc::SIGINT))?;
+                continue;
+            }
+            Line::Eof if eofs < ignore_eof() => {
+                eofs += 1;
+                eprintln!("Use \"exit\" to leave the shell.");
+                continue;
+            }
+            Line::Eof => {
+                writeln!(term, "exit")?;
+                return Ok(vars::last_status());
+            }
+        };
+        eofs = 0;
 
-        while let Ok(key) = term.read_key() {
-            match key {
-                Key::Enter => {
-                    writeln!(term)?;
-                    break;
+        let input = match history::expand(&line) {
+            Ok(expanded) => {
+                if expanded.changed {
+                    writeln!(term, "{}", expanded.line)?;
                 }
-                Key::Char(c) => {
-                    write!(term, "{c}")?;
-                    buf.push(c as u8);
-                }
-                Key::Tab => {
-                    let input = std::str::from_utf8(&buf)?;
-
-                    if candidates.is_empty() {
-                        candidates = Command::autocomplete(input);
-                        candidates.sort();
-
-                        if candidates.len() == 1 {
-                            if let Some(cmd) = candidates.pop() {
-                                term.clear_line()?;
-
-                                let completed = format!("{cmd} ");
-                                write!(term, "$ {completed}")?;
-                                buf = completed.into_bytes();
-                            }
-                        } else {
-                            let common = common_parts(&candidates);
-
-                            if input.len() < common.len() {
-                                term.clear_line()?;
-
-                                write!(term, "$ {common}")?;
-                                buf = common.into_bytes();
-                            } else {
-                                write!(term, "\x07")?;
-                            }
-                        }
-                    } else {
-                        writeln!(term, "\n{}", candidates.join("  "))?;
-                        write!(term, "$ {input}")?;
-                    }
-
+                if expanded.print_only {
+                    history::push(&expanded.line);
                     continue;
                 }
-                Key::Backspace => {
-                    term.clear_chars(1)?;
-                    buf.pop();
-                }
-                _ => {}
+                expanded.line
+            }
+            Err(msg) => {
+                eprintln!("{msg}");
+                continue;
             }
+        };
 
-            candidates = vec![];
-        }
+        history::push(&input);
+        run_lines(input.as_bytes(), &f)?;
+    }
+}
+
+/// How many Ctrl-D in a row are ignored before the shell exits: none when
+/// `$IGNOREEOF` is unset, and 10 when it isn't a number.
+fn ignore_eof() -> usize {
+    match std::env::var("IGNOREEOF") {
+        Ok(value) => value.parse().unwrap_or(10),
+        Err(_) => 0,
+    }
+}
 
-        let input = std::str::from_utf8(&buf)?;
+/// Runs one command line, firing the DEBUG and ERR traps around it and the
+/// traps of any signal caught meanwhile.
+pub fn exec_cmd(inputs: &str) -> Result<i32> {
+    trap::run_pending()?;
 
-        f(input.trim())?;
+    if inputs.is_empty() {
+        return Ok(0);
     }
+
+    trap::run(trap::Condition::Debug)?;
+    let status = exec(inputs)?;
+    vars::set_last_status(status);
+
+    if status != 0 {
+        trap::run(trap::Condition::Err)?;
+    }
+
+    trap::run_pending()?;
+    Ok(status)
 }
 
-pub fn exec_cmd(inputs: &str) -> Result<()> {
-    let input = Inputs::parse(inputs);
+fn exec(inputs: &str) -> Result<i32> {
+    match Definition::parse(inputs) {
+        Some(Ok(definition)) => {
+            function::define(definition);
+            return Ok(0);
+        }
+        Some(Err(msg)) => {
+            Writer::builder().build()?.ewriteln(msg)?;
+            return Ok(2);
+        }
+        None => {}
+    }
+
+    match Assignment::parse(inputs) {
+        Some(Assignment::Scalars(scalars)) => {
+            for (name, value) in scalars {
+                vars::set(&name, value);
+            }
+            return Ok(0);
+        }
+        Some(Assignment::Array(name, values)) => {
+            vars::set_array(&name, values);
+            return Ok(0);
+        }
+        None => {}
+    }
+
+    let input = Inputs::parse(&cmd::alias::expand(inputs));
     let mut writer = input.writer()?;
     Command::new(input.args).run(&mut writer)
 }
 
 fn common_parts(names: &[String]) -> String {
     if let Some(name) = names.first() {
-        for i in 1..(name.len() + 1) {
-            let pattern = &name[..i];
+        for (i, c) in name.char_indices() {
+            let pattern = &name[..i + c.len_utf8()];
 
             if !names.iter().all(|n| n.starts_with(pattern)) {
-                return name[..i - 1].to_string();
+                return name[..i].to_string();
             }
         }
 
@@ -135,5 +310,9 @@ mod tests {
         ];
         let subject = common_parts(&names);
         assert_eq!(subject, "foo_");
+
+        let names = vec!["café".to_string(), "cafè".to_string()];
+        let subject = common_parts(&names);
+        assert_eq!(subject, "caf");
     }
 }
@@ -1,8 +1,19 @@
-use codecrafters_shell::{exec_cmd, repl};
+use codecrafters_shell::{exit, run, Options};
 
 fn main() {
-    if let Err(err) = repl(exec_cmd) {
-        eprintln!("{err}");
-        std::process::exit(1);
+    let opts = match Options::parse(std::env::args()) {
+        Ok(opts) => opts,
+        Err(err) => {
+            eprintln!("{err}");
+            exit(2);
+        }
+    };
+
+    match run(opts) {
+        Ok(status) => exit(status),
+        Err(err) => {
+            eprintln!("{err}");
+            exit(1);
+        }
     }
 }
@@ -0,0 +1,177 @@
+use crate::Result;
+
+use std::path::PathBuf;
+
+/// Where the shell reads its commands from.
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    /// `-s`, or no operands: standard input, interactively when it is a terminal.
+    Stdin,
+    /// `-c string`
+    Command(String),
+    /// The first operand names a script file.
+    Script(PathBuf),
+}
+
+/// Which startup files the shell reads.
+#[derive(Debug, Default, PartialEq)]
+pub struct Startup {
+    /// `-l`, `--login`, or an `argv[0]` starting with `-`: read
+    /// `/etc/profile` and `~/.profile`.
+    pub login: bool,
+    /// `--noprofile`
+    pub noprofile: bool,
+    /// `--norc`
+    pub norc: bool,
+    /// `--rcfile FILE`, read instead of `$SHELLRC` or `~/.shellrc`.
+    pub rcfile: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub mode: Mode,
+    /// `$0`
+    pub name: String,
+    /// `$1`, `$2`, ...
+    pub args: Vec<String>,
+    pub startup: Startup,
+}
+
+impl Options {
+    /// Parses the command line, `argv[0]` included.
+    pub fn parse(argv: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut argv = argv.into_iter();
+        let name = argv.next().unwrap_or_default();
+        let mut command = false;
+        let mut stdin = false;
+        let mut startup = Startup {
+            login: name.starts_with('-'),
+            ..Startup::default()
+        };
+
+        let mut operands: Vec<String> = vec![];
+
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--" => break,
+                "-c" => command = true,
+                "-s" => stdin = true,
+                "-l" | "--login" => startup.login = true,
+                "--noprofile" => startup.noprofile = true,
+                "--norc" => startup.norc = true,
+                "--rcfile" => {
+                    let file = argv
+                        .next()
+                        .ok_or(err!("--rcfile: option requires an argument"))?;
+                    startup.rcfile = Some(PathBuf::from(file));
+                }
+                opt if opt.len() > 1 && opt.starts_with('-') => {
+                    return Err(err!("{opt}: invalid option"));
+                }
+                _ => {
+                    operands.push(arg);
+                    break;
+                }
+            }
+        }
+        operands.extend(argv);
+
+        let mut operands = operands.into_iter();
+
+        let opts = if command {
+            let cmds = operands
+                .next()
+                .ok_or(err!("-c: option requires an argument"))?;
+            Self {
+                mode: Mode::Command(cmds),
+                name: operands.next().unwrap_or(name),
+                args: operands.collect(),
+                startup,
+            }
+        } else if stdin {
+            Self {
+                mode: Mode::Stdin,
+                name,
+                args: operands.collect(),
+                startup,
+            }
+        } else {
+            match operands.next() {
+                Some(script) => Self {
+                    mode: Mode::Script(PathBuf::from(&script)),
+                    name: script,
+                    args: operands.collect(),
+                    startup,
+                },
+                None => Self {
+                    mode: Mode::Stdin,
+                    name,
+                    args: vec![],
+                    startup,
+                },
+            }
+        };
+
+        Ok(opts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Options {
+        Options::parse(args.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn it_parses_options() {
+        let subject = parse(&["shell"]);
+        assert_eq!(subject.mode, Mode::Stdin);
+        assert_eq!(subject.name, "shell");
+
+        let subject = parse(&["shell", "run.sh", "a", "-c"]);
+        assert_eq!(subject.mode, Mode::Script("run.sh".into()));
+        assert_eq!(subject.name, "run.sh");
+        assert_eq!(subject.args, vec!["a", "-c"]);
+
+        let subject = parse(&["shell", "-c", "echo $1", "name", "a"]);
+        assert_eq!(subject.mode, Mode::Command("echo $1".into()));
+        assert_eq!(subject.name, "name");
+        assert_eq!(subject.args, vec!["a"]);
+
+        let subject = parse(&["shell", "-s", "a", "b"]);
+        assert_eq!(subject.mode, Mode::Stdin);
+        assert_eq!(subject.args, vec!["a", "b"]);
+
+        let subject = Options::parse(["shell".to_string(), "-c".to_string()]);
+        assert!(subject.is_err());
+
+        let subject = Options::parse(["shell".to_string(), "-x".to_string()]);
+        assert!(subject.is_err());
+    }
+
+    #[test]
+    fn it_parses_startup_options() {
+        let subject = parse(&["shell"]);
+        assert_eq!(subject.startup, Startup::default());
+
+        let subject = parse(&["-shell"]);
+        assert!(subject.startup.login);
+
+        let subject = parse(&["shell", "--login", "--norc", "--rcfile", "rc", "-c", "true"]);
+        assert_eq!(
+            subject.startup,
+            Startup {
+                login: true,
+                noprofile: false,
+                norc: true,
+                rcfile: Some("rc".into()),
+            }
+        );
+        assert_eq!(subject.mode, Mode::Command("true".into()));
+
+        let subject = Options::parse(["shell".to_string(), "--rcfile".to_string()]);
+        assert!(subject.is_err());
+    }
+}
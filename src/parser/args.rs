@@ -1,3 +1,7 @@
+use crate::cmd::fs;
+
+use std::ops::Range;
+
 const SINGLE_QUOTE: char = '\'';
 const DOUBLE_QUOTE: char = '"';
 
@@ -21,9 +25,9 @@ impl Iterator for Args<'_> {
         }
 
         let (mut token, mut rest) = split_token(self.inner);
-        let mut str = token;
+        let mut str = expand_tilde(self.inner, token);
 
-        while !rest.is_empty() && !rest.starts_with(' ') {
+        while !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
             (token, rest) = split_token(rest.trim());
             str.push_str(&token);
         }
@@ -39,6 +43,111 @@ impl<'a> From<&'a str> for Args<'a> {
     }
 }
 
+/// A word of a line as it was typed, for completion.
+#[derive(Debug, Default, PartialEq)]
+pub struct Token {
+    /// Where the word is in the line.
+    pub span: Range<usize>,
+    /// The word with its quotes and escapes removed, but nothing expanded.
+    pub text: String,
+    /// The quote still open at the end of the line, if any.
+    pub quote: Option<char>,
+}
+
+/// Splits `line` into words the way `Args` does, keeping where each of
+/// them is rather than expanding them.
+pub fn tokens(line: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut current: Option<Token> = None;
+    let mut quote = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if quote.is_none() && c.is_whitespace() {
+            tokens.extend(current.take());
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| Token {
+            span: i..i,
+            ..Token::default()
+        });
+
+        match (quote, c) {
+            (None, SINGLE_QUOTE | DOUBLE_QUOTE) => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '\\') => token.text.extend(chars.next().map(|(_, c)| c)),
+            (Some(DOUBLE_QUOTE), '\\') => match chars.peek() {
+                Some(&(_, c @ ('\\' | '$' | '"' | '\n'))) => {
+                    token.text.push(c);
+                    chars.next();
+                }
+                _ => token.text.push('\\'),
+            },
+            (_, c) => token.text.push(c),
+        }
+
+        token.span.end = chars.peek().map_or(line.len(), |&(j, _)| j);
+    }
+
+    if let Some(mut token) = current {
+        token.quote = quote;
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Expands a `~` or `~user` starting a word, up to the first `/`, to
+/// `$HOME` or the user's home directory, given the word and its first
+/// token. A name with any quoting in it is left alone.
+fn expand_tilde(word: &str, token: String) -> String {
+    let Some(rest) = word.strip_prefix('~') else {
+        return token;
+    };
+    let end = rest
+        .find(|c: char| c == '/' || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let user = &rest[..end];
+
+    if token.get(1..=end) != Some(user) {
+        return token;
+    }
+
+    match fs::home_dir(user) {
+        Some(home) => format!("{home}{}", &token[1 + end..]),
+        None => token,
+    }
+}
+
+/// Splits `text` into commands at the `;` and newlines that aren't quoted
+/// or escaped, leaving out blank ones.
+pub fn commands(text: &str) -> Vec<&str> {
+    let mut commands = vec![];
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, SINGLE_QUOTE | DOUBLE_QUOTE) => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None | Some(DOUBLE_QUOTE), '\\') => {
+                chars.next();
+            }
+            (None, ';' | '\n') => {
+                commands.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    commands.push(text[start..].trim());
+
+    commands.retain(|command| !command.is_empty());
+    commands
+}
+
 fn split_token(str: &str) -> (String, &str) {
     if str.starts_with(SINGLE_QUOTE) {
         split_quoted(SINGLE_QUOTE, str)
@@ -87,6 +196,9 @@ fn split_double_quoted(token: &str) -> (String, &str) {
                     tokens.push(c);
                 }
             }
+        } else if let Some((value, len)) = expand_param(c, &token[(idx + c.len_utf8())..]) {
+            tokens.extend(value.chars());
+            chars.nth(len - 1);
         } else {
             tokens.push(c);
         }
@@ -108,6 +220,9 @@ fn split_not_quoted(token: &str) -> (String, &str) {
             if let Some((_, c)) = chars.next() {
                 tokens.push(c);
             }
+        } else if let Some((value, len)) = expand_param(c, &token[(idx + c.len_utf8())..]) {
+            tokens.extend(value.chars());
+            chars.nth(len - 1);
         } else {
             tokens.push(c);
         }
@@ -116,6 +231,68 @@ fn split_not_quoted(token: &str) -> (String, &str) {
     (tokens.into_iter().collect(), "")
 }
 
+/// Expands `$name`, `${name}`, an array element as in `${name[1]}` or a
+/// special parameter when `c` is a `$`. Returns the value along with the
+/// number of chars of `rest` it consumed. Expansions are not split into
+/// words.
+fn expand_param(c: char, rest: &str) -> Option<(String, usize)> {
+    if c != '$' {
+        return None;
+    }
+
+    let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+        let name = &braced[..braced.find('}')?];
+        let len = name.chars().count() + 2;
+        if let Some((name, index)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            return Some((expand_element(name, index).unwrap_or_default(), len));
+        }
+        (name, len)
+    } else {
+        let name = match rest.chars().next()? {
+            c if c.is_ascii_digit() || "#@*?$".contains(c) => &rest[..1],
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                &rest[..len]
+            }
+            _ => return None,
+        };
+        (name, name.len())
+    };
+
+    Some((crate::vars::get(name).unwrap_or_default(), len))
+}
+
+/// Expands `${name[index]}`, where the index is a number or a variable
+/// holding one. `@` and `*` stand for every element, joined by spaces, and
+/// `${#name[@]}` is their number.
+fn expand_element(name: &str, index: &str) -> Option<String> {
+    let (count, name) = match name.strip_prefix('#') {
+        Some(name) => (true, name),
+        None => (false, name),
+    };
+    let values = crate::vars::array(name).unwrap_or_default();
+
+    match index {
+        "@" | "*" if count => Some(values.len().to_string()),
+        "@" | "*" => Some(values.join(" ")),
+        _ => {
+            let index = index.trim().trim_start_matches('$');
+            let index = match index.parse::<usize>() {
+                Ok(index) => index,
+                Err(_) => crate::vars::get(index)?.parse().ok()?,
+            };
+            let value = values.into_iter().nth(index)?;
+            Some(if count {
+                value.chars().count().to_string()
+            } else {
+                value
+            })
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +322,46 @@ mod tests {
         assert_eq!(args.next(), Some("shell     test".into()));
         assert_eq!(args.next(), Some("examplehelloworld".into()));
         assert_eq!(args.next(), None);
+
+        let mut args = Args::new("one\t\"two\"\tthree");
+        assert_eq!(args.next(), Some("one".into()));
+        assert_eq!(args.next(), Some("two".into()));
+        assert_eq!(args.next(), Some("three".into()));
+        assert_eq!(args.next(), None);
+
+        let _state = crate::vars::test_lock();
+        let mut args = Args::new("café \"日本\" 日$?");
+        assert_eq!(args.next(), Some("café".into()));
+        assert_eq!(args.next(), Some("日本".into()));
+        assert_eq!(args.next(), Some("日0".into()));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn it_expands_tildes() {
+        if let Some(user) = fs::current_user() {
+            let home = fs::home_dir(&user).unwrap();
+            let line = format!("~{user}/src ~{user}");
+            let mut args = Args::new(&line);
+            assert_eq!(args.next(), Some(format!("{home}/src")));
+            assert_eq!(args.next(), Some(home));
+            assert_eq!(args.next(), None);
+        }
+
+        let mut args = Args::new("~no-such-user/a ~\"root\" a~");
+        assert_eq!(args.next(), Some("~no-such-user/a".into()));
+        assert_eq!(args.next(), Some("~root".into()));
+        assert_eq!(args.next(), Some("a~".into()));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn it_splits_commands() {
+        assert_eq!(
+            commands("echo a; echo 'b;c' \"d\\\";\"\\;\n\n ls ;"),
+            vec!["echo a", "echo 'b;c' \"d\\\";\"\\;", "ls"]
+        );
+        assert_eq!(commands(" ; "), Vec::<&str>::new());
     }
 
     #[test]
@@ -170,6 +387,79 @@ mod tests {
         assert_eq!(rest, "");
     }
 
+    #[test]
+    fn it_expands_parameters() {
+        std::env::set_var("ARGS_TEST_VAR", "foo");
+        std::env::remove_var("ARGS_TEST_UNSET");
+
+        let mut args = Args::new("a$ARGS_TEST_VAR \"${ARGS_TEST_VAR}b\" \'$ARGS_TEST_VAR\'");
+        assert_eq!(args.next(), Some("afoo".into()));
+        assert_eq!(args.next(), Some("foob".into()));
+        assert_eq!(args.next(), Some("$ARGS_TEST_VAR".into()));
+        assert_eq!(args.next(), None);
+
+        let mut args = Args::new("\\$ARGS_TEST_VAR \"\\$x\" $ARGS_TEST_UNSET- $ ${");
+        assert_eq!(args.next(), Some("$ARGS_TEST_VAR".into()));
+        assert_eq!(args.next(), Some("$x".into()));
+        assert_eq!(args.next(), Some("-".into()));
+        assert_eq!(args.next(), Some("$".into()));
+        assert_eq!(args.next(), Some("${".into()));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn it_expands_array_elements() {
+        crate::vars::set_array("ARGS_TEST_ARRAY", vec!["a".into(), "b c".into()]);
+        crate::vars::set_array("ARGS_TEST_INDEX", vec!["1".into()]);
+
+        let line = "${ARGS_TEST_ARRAY[0]} \"${ARGS_TEST_ARRAY[ARGS_TEST_INDEX]}\" \
+            \"${ARGS_TEST_ARRAY[@]}\" ${#ARGS_TEST_ARRAY[@]} ${#ARGS_TEST_ARRAY[1]} \
+            x${ARGS_TEST_ARRAY[2]} $ARGS_TEST_ARRAY";
+        let mut args = Args::new(line);
+        assert_eq!(args.next(), Some("a".into()));
+        assert_eq!(args.next(), Some("b c".into()));
+        assert_eq!(args.next(), Some("a b c".into()));
+        assert_eq!(args.next(), Some("2".into()));
+        assert_eq!(args.next(), Some("3".into()));
+        assert_eq!(args.next(), Some("x".into()));
+        assert_eq!(args.next(), Some("a".into()));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn it_expands_tilde() {
+        let home = std::env::var("HOME").unwrap();
+
+        let mut args = Args::new("~ ~/src a~ ~user \\~ '~'");
+        assert_eq!(args.next(), Some(home.clone()));
+        assert_eq!(args.next(), Some(format!("{home}/src")));
+        assert_eq!(args.next(), Some("a~".into()));
+        assert_eq!(args.next(), Some("~user".into()));
+        assert_eq!(args.next(), Some("~".into()));
+        assert_eq!(args.next(), Some("~".into()));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn it_splits_tokens() {
+        let split = tokens(" cat  'a b'\\ c\"d\\\"\" \"$HOME");
+        let spans: Vec<(Range<usize>, &str, Option<char>)> = split
+            .iter()
+            .map(|t| (t.span.clone(), t.text.as_str(), t.quote))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (1..4, "cat", None),
+                (6..19, "a b cd\"", None),
+                (20..26, "$HOME", Some('"')),
+            ]
+        );
+
+        assert_eq!(tokens("café 'x").last().unwrap().span, 6..8);
+        assert!(tokens("  ").is_empty());
+    }
+
     #[test]
     fn it_splits_not_quoted_strings() {
         let str = "world\\ \\ \\ \\ \\ \\ script";
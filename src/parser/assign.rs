@@ -0,0 +1,73 @@
+use super::{args::Args, tokens};
+
+/// A line that only assigns variables: `name=value ...`, or `name=(word ...)`
+/// for an array.
+#[derive(Debug, PartialEq)]
+pub enum Assignment {
+    Scalars(Vec<(String, String)>),
+    Array(String, Vec<String>),
+}
+
+impl Assignment {
+    /// Parses `line` as assignments, or returns `None` if any of its words
+    /// isn't one. The name has to be unquoted, and the value is expanded as
+    /// a single word.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+
+        if let Some((name, rest)) = line.split_once("=(") {
+            if is_name(name) {
+                let words = rest.strip_suffix(')')?;
+                return Some(Self::Array(name.to_string(), Args::new(words).collect()));
+            }
+        }
+
+        let mut scalars = vec![];
+        for token in tokens(line) {
+            let word = &line[token.span];
+            let (name, value) = word.split_once('=')?;
+            if !is_name(name) {
+                return None;
+            }
+            scalars.push((name.to_string(), Args::new(value).collect()));
+        }
+
+        (!scalars.is_empty()).then_some(Self::Scalars(scalars))
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_assignments() {
+        assert_eq!(
+            Assignment::parse("a=1 _b='x y' c="),
+            Some(Assignment::Scalars(vec![
+                ("a".into(), "1".into()),
+                ("_b".into(), "x y".into()),
+                ("c".into(), "".into()),
+            ]))
+        );
+        assert_eq!(
+            Assignment::parse("REPLY=(start \"st op\" '')"),
+            Some(Assignment::Array(
+                "REPLY".into(),
+                vec!["start".into(), "st op".into(), "".into()]
+            ))
+        );
+
+        assert_eq!(Assignment::parse("a=1 echo"), None);
+        assert_eq!(Assignment::parse("'a'=1"), None);
+        assert_eq!(Assignment::parse("1a=1"), None);
+        assert_eq!(Assignment::parse("echo a=1"), None);
+        assert_eq!(Assignment::parse("a=(b"), None);
+        assert_eq!(Assignment::parse(""), None);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::Result;
 
 mod args;
+mod assign;
 
 use super::writer::Writer;
 use args::Args;
+pub use args::{commands, tokens, Token};
+pub use assign::Assignment;
 
 #[derive(Debug)]
 pub struct Inputs {
@@ -0,0 +1,208 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+
+use libc::c_int;
+
+/// Process group id of the job currently running in the foreground, or 0 at the prompt.
+static FOREGROUND: AtomicI32 = AtomicI32::new(0);
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+/// Bit set of signals received since the last call to [`take_pending`].
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+pub const SIGNALS: [(&str, c_int); 29] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+const FORWARDED: [c_int; 3] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP];
+const IGNORED: [c_int; 2] = [libc::SIGTTIN, libc::SIGTTOU];
+
+/// Keeps the interactive shell alive on job-control signals.
+///
+/// SIGINT, SIGQUIT and SIGTSTP are forwarded to the foreground job instead of
+/// terminating the shell. A handler (rather than `SIG_IGN`) is installed so
+/// that spawned programs start with the default dispositions again.
+pub fn init() {
+    INTERACTIVE.store(is_tty(), Ordering::SeqCst);
+
+    for sig in FORWARDED {
+        catch(sig);
+    }
+
+    for sig in IGNORED {
+        ignore(sig);
+    }
+}
+
+/// Resets the dispositions the shell ignores for its own sake. Caught signals
+/// are reset by `exec` anyway, and signals ignored with `trap '' SIG` are meant
+/// to stay ignored. Meant to run in a child between `fork` and `exec`, so it
+/// only calls async-signal-safe functions.
+pub fn restore_defaults() {
+    for sig in IGNORED {
+        unsafe {
+            libc::signal(sig, libc::SIG_DFL);
+        }
+    }
+}
+
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::SeqCst)
+}
+
+/// Marks `pgid` as the foreground job and hands it the terminal.
+pub fn set_foreground(pgid: i32) {
+    FOREGROUND.store(pgid, Ordering::SeqCst);
+
+    if is_interactive() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        }
+    }
+}
+
+/// Takes the terminal back after the foreground job finished or stopped.
+pub fn reclaim_terminal() {
+    FOREGROUND.store(0, Ordering::SeqCst);
+
+    if is_interactive() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        }
+    }
+}
+
+/// Looks a signal up by name (`INT`, `SIGINT`, `int`) or by number.
+pub fn from_name(name: &str) -> Option<c_int> {
+    if let Ok(num) = name.parse::<c_int>() {
+        return SIGNALS.iter().find(|(_, n)| *n == num).map(|(_, n)| *n);
+    }
+
+    let name = name.to_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS.iter().find(|(n, _)| *n == name).map(|(_, n)| *n)
+}
+
+pub fn name(sig: c_int) -> Option<&'static str> {
+    SIGNALS.iter().find(|(_, n)| *n == sig).map(|(n, _)| *n)
+}
+
+/// Describes `sig` the way job notifications do, e.g. "Terminated".
+pub fn describe(sig: c_int) -> String {
+    let desc = unsafe { libc::strsignal(sig) };
+
+    if desc.is_null() {
+        format!("Signal {sig}")
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(desc) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Formats the signal table printed by `trap -l` and `kill -l`.
+pub fn table() -> Vec<String> {
+    SIGNALS
+        .chunks(5)
+        .map(|row| {
+            row.iter()
+                .map(|(name, num)| format!("{num:2}) SIG{name}"))
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect()
+}
+
+/// Records `sig` in the pending set when it arrives, see [`take_pending`].
+pub fn catch(sig: c_int) {
+    install(sig, handle as extern "C" fn(c_int) as libc::sighandler_t);
+}
+
+pub fn ignore(sig: c_int) {
+    install(sig, libc::SIG_IGN);
+}
+
+/// Restores the disposition the shell starts with for `sig`.
+pub fn reset(sig: c_int) {
+    match is_interactive() {
+        true if FORWARDED.contains(&sig) => catch(sig),
+        true if IGNORED.contains(&sig) => ignore(sig),
+        _ => install(sig, libc::SIG_DFL),
+    }
+}
+
+/// Returns the signals received since the last call, in ascending order.
+pub fn take_pending() -> Vec<c_int> {
+    let bits = PENDING.swap(0, Ordering::SeqCst);
+    (0..64).filter(|sig| bits & (1 << sig) != 0).collect()
+}
+
+fn is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+fn install(sig: c_int, handler: libc::sighandler_t) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut());
+    }
+}
+
+extern "C" fn handle(sig: c_int) {
+    PENDING.fetch_or(1 << sig, Ordering::SeqCst);
+
+    let pgid = FOREGROUND.load(Ordering::SeqCst);
+
+    if pgid > 0 && FORWARDED.contains(&sig) {
+        unsafe {
+            libc::kill(-pgid, sig);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_looks_up_signals() {
+        assert_eq!(from_name("INT"), Some(libc::SIGINT));
+        assert_eq!(from_name("SIGTERM"), Some(libc::SIGTERM));
+        assert_eq!(from_name("usr1"), Some(libc::SIGUSR1));
+        assert_eq!(from_name("9"), Some(libc::SIGKILL));
+        assert_eq!(from_name("FOO"), None);
+        assert_eq!(from_name("0"), None);
+
+        assert_eq!(name(libc::SIGHUP), Some("HUP"));
+        assert_eq!(name(0), None);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::options::Startup;
+
+use std::path::PathBuf;
+
+/// Sources `/etc/profile` and `~/.profile` for login shells, and then
+/// `--rcfile`, `$SHELLRC` or `~/.shellrc` for interactive ones. Files that
+/// don't exist are skipped, unless named with `--rcfile`.
+pub fn run(startup: &Startup, interactive: bool) {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+
+    if startup.login && !startup.noprofile {
+        for path in [PathBuf::from("/etc/profile"), home.join(".profile")] {
+            source(path, false);
+        }
+    }
+
+    if interactive && !startup.norc {
+        match (&startup.rcfile, std::env::var_os("SHELLRC")) {
+            (Some(path), _) => source(path.clone(), true),
+            (None, Some(path)) => source(PathBuf::from(path), false),
+            (None, None) => source(home.join(".shellrc"), false),
+        }
+    }
+}
+
+fn source(path: PathBuf, required: bool) {
+    if !path.is_file() {
+        if required {
+            eprintln!("{}: No such file or directory", path.display());
+        }
+        return;
+    }
+
+    if let Err(err) = crate::source(&path) {
+        eprintln!("{}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::alias;
+
+    #[test]
+    fn it_sources_the_rc_file() {
+        let _state = crate::vars::test_lock();
+        let rc = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(rc.path(), "# aliases\nalias startup-test-ll='ls -l'\n").unwrap();
+        let startup = Startup {
+            rcfile: Some(rc.path().to_path_buf()),
+            ..Startup::default()
+        };
+
+        run(&startup, false);
+        assert_eq!(alias::get("startup-test-ll"), None);
+
+        let norc = Startup {
+            norc: true,
+            rcfile: startup.rcfile.clone(),
+            ..Startup::default()
+        };
+        run(&norc, true);
+        assert_eq!(alias::get("startup-test-ll"), None);
+
+        run(&startup, true);
+        assert_eq!(alias::get("startup-test-ll"), Some("ls -l".into()));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{signal, Result};
+
+use libc::c_int;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static TRAPS: Mutex<BTreeMap<Condition, String>> = Mutex::new(BTreeMap::new());
+/// Set while a trap action runs so that actions don't trigger DEBUG/ERR traps themselves.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    Exit,
+    Signal(c_int),
+    Debug,
+    Err,
+    Return,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Command(String),
+    Ignore,
+    Default,
+}
+
+impl Condition {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "0" | "EXIT" => Some(Self::Exit),
+            "DEBUG" => Some(Self::Debug),
+            "ERR" => Some(Self::Err),
+            "RETURN" => Some(Self::Return),
+            _ => signal::from_name(s).map(Self::Signal),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exit => write!(f, "EXIT"),
+            Self::Debug => write!(f, "DEBUG"),
+            Self::Err => write!(f, "ERR"),
+            Self::Return => write!(f, "RETURN"),
+            Self::Signal(sig) => write!(f, "SIG{}", signal::name(*sig).unwrap_or_default()),
+        }
+    }
+}
+
+pub fn set(cond: Condition, action: Action) {
+    if let Condition::Signal(sig) = cond {
+        match action {
+            Action::Command(_) => signal::catch(sig),
+            Action::Ignore => signal::ignore(sig),
+            Action::Default => signal::reset(sig),
+        }
+    }
+
+    let mut traps = lock();
+    match action {
+        Action::Command(cmd) => traps.insert(cond, cmd),
+        Action::Ignore => traps.insert(cond, String::new()),
+        Action::Default => traps.remove(&cond),
+    };
+}
+
+/// Returns every trap currently set, in `trap -p` order.
+pub fn list() -> Vec<(Condition, String)> {
+    lock().iter().map(|(c, a)| (*c, a.clone())).collect()
+}
+
+pub fn get(cond: Condition) -> Option<String> {
+    lock().get(&cond).cloned()
+}
+
+/// Runs the action trapped for `cond`, if any.
+pub fn run(cond: Condition) -> Result<()> {
+    if RUNNING.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    match get(cond) {
+        Some(action) => run_action(&action),
+        None => Ok(()),
+    }
+}
+
+/// Runs the actions for the signals caught since the last call.
+pub fn run_pending() -> Result<()> {
+    for sig in signal::take_pending() {
+        run(Condition::Signal(sig))?;
+    }
+    Ok(())
+}
+
+/// Runs the EXIT trap, at most once however many times the shell exits.
+pub fn run_exit() {
+    let action = lock().remove(&Condition::Exit);
+
+    if let Some(action) = action {
+        if let Err(err) = run_action(&action) {
+            eprintln!("{err}");
+        }
+    }
+}
+
+fn run_action(action: &str) -> Result<()> {
+    RUNNING.store(true, Ordering::SeqCst);
+    let result = action
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .try_for_each(|line| crate::exec(line).map(|_| ()));
+    RUNNING.store(false, Ordering::SeqCst);
+
+    result
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<Condition, String>> {
+    TRAPS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_conditions() {
+        assert_eq!(Condition::parse("EXIT"), Some(Condition::Exit));
+        assert_eq!(Condition::parse("0"), Some(Condition::Exit));
+        assert_eq!(Condition::parse("err"), Some(Condition::Err));
+        assert_eq!(
+            Condition::parse("SIGINT"),
+            Some(Condition::Signal(libc::SIGINT))
+        );
+        assert_eq!(
+            Condition::parse("term"),
+            Some(Condition::Signal(libc::SIGTERM))
+        );
+        assert_eq!(Condition::parse("FOO"), None);
+    }
+
+    #[test]
+    fn it_displays_conditions() {
+        assert_eq!(Condition::Signal(libc::SIGINT).to_string(), "SIGINT");
+        assert_eq!(Condition::Return.to_string(), "RETURN");
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+/// `$0` followed by the positional parameters `$1`, `$2`, ...
+static ARGV: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// `$?`
+static STATUS: AtomicI32 = AtomicI32::new(0);
+/// Shell variables, which unlike environment variables aren't passed on to
+/// commands. One that isn't an array has a single element.
+static VARS: Mutex<BTreeMap<String, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+pub fn set_name(name: String) {
+    let mut argv = lock();
+    match argv.first_mut() {
+        Some(first) => *first = name,
+        None => argv.push(name),
+    }
+}
+
+/// Replaces the positional parameters, returning the previous ones.
+pub fn set_positional(args: Vec<String>) -> Vec<String> {
+    let mut argv = lock();
+    if argv.is_empty() {
+        argv.push(String::new());
+    }
+    argv.splice(1.., args).collect()
+}
+
+pub fn positional() -> Vec<String> {
+    lock().iter().skip(1).cloned().collect()
+}
+
+pub fn last_status() -> i32 {
+    STATUS.load(Ordering::SeqCst)
+}
+
+pub fn set_last_status(status: i32) {
+    STATUS.store(status, Ordering::SeqCst);
+}
+
+/// Sets `name` to `value`: the environment variable when there is one, so
+/// that commands see the change, and a shell variable otherwise.
+pub fn set(name: &str, value: String) {
+    if std::env::var_os(name).is_some() {
+        std::env::set_var(name, value);
+    } else {
+        vars().insert(name.to_string(), vec![value]);
+    }
+}
+
+/// Makes `name` a shell variable holding `values`, taking it out of the
+/// environment.
+pub fn set_array(name: &str, values: Vec<String>) {
+    std::env::remove_var(name);
+    vars().insert(name.to_string(), values);
+}
+
+/// The elements of `name`, of which a variable that isn't an array has one.
+pub fn array(name: &str) -> Option<Vec<String>> {
+    match vars().get(name) {
+        Some(values) => Some(values.clone()),
+        None => Some(vec![std::env::var(name).ok()?]),
+    }
+}
+
+pub fn unset(name: &str) {
+    vars().remove(name);
+    std::env::remove_var(name);
+}
+
+/// Looks up a parameter: special parameters (`0`-`9`, `#`, `@`, `*`, `?`,
+/// `$`) first, then shell variables, whose first element stands for an
+/// array, then environment variables.
+pub fn get(name: &str) -> Option<String> {
+    match name {
+        "#" => Some(positional().len().to_string()),
+        "@" | "*" => Some(positional().join(" ")),
+        "?" => Some(last_status().to_string()),
+        "$" => Some(std::process::id().to_string()),
+        _ => match name.parse::<usize>() {
+            Ok(n) => lock().get(n).cloned(),
+            Err(_) => match vars().get(name) {
+                Some(values) => values.first().cloned(),
+                None => std::env::var(name).ok(),
+            },
+        },
+    }
+}
+
+fn vars() -> std::sync::MutexGuard<'static, BTreeMap<String, Vec<String>>> {
+    VARS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Vec<String>> {
+    ARGV.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Held by tests that change or rely on what the whole shell shares: `$?`,
+/// the positional parameters and the working directory.
+#[cfg(test)]
+pub fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
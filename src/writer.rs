@@ -2,6 +2,19 @@ use crate::{Error, Result};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
 use std::path::Path;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+/// Where the command being run was read from, as `file: line N`, while a
+/// file is being sourced.
+static LOCATION: Mutex<Option<String>> = Mutex::new(None);
+
+/// Sets where commands are read from, returning where they were read from
+/// before.
+pub fn set_location(location: Option<String>) -> Option<String> {
+    let mut current = LOCATION.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::replace(&mut current, location)
+}
 
 #[derive(Debug, Default)]
 pub struct WriterBuilder<'a> {
@@ -107,8 +120,29 @@ impl Writer {
         Ok(())
     }
 
+    /// Writes a line to stderr, after the file and line the command came
+    /// from when it's in a sourced file.
     pub fn ewriteln<T: AsRef<[u8]>>(&mut self, buf: T) -> Result<()> {
+        let location = LOCATION.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(location) = location {
+            self.ewrite(format!("{location}: "))?;
+        }
         self.ewrite(buf)?;
         self.ewrite(b"\n")
     }
+
+    pub fn stdout_stdio(&self) -> Result<Stdio> {
+        stdio(self.stdout.as_ref())
+    }
+
+    pub fn stderr_stdio(&self) -> Result<Stdio> {
+        stdio(self.stderr.as_ref())
+    }
+}
+
+fn stdio(file: Option<&File>) -> Result<Stdio> {
+    match file {
+        Some(f) => Ok(f.try_clone()?.into()),
+        None => Ok(Stdio::inherit()),
+    }
 }